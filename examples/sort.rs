@@ -3,7 +3,7 @@ use parquet_ops;
 use std::any::type_name;
 use std::cmp::Ordering;
 use std::time::Instant;
-use std::{fs, io::Read};
+use std::{env, fs, io::Read};
 
 // return the type of a ref as a static string
 fn type_of<T>(_: &T) -> &'static str {
@@ -25,10 +25,18 @@ fn main() {
     let action = "sorting";
     let path_1 = paths::PATH_1;
     let sorted_path = "sorted.parquet";
+    let num_workers = env::args()
+        .skip(1)
+        .next()
+        .map_or(1, |s| s.parse::<usize>().expect("first argument should be 'num_workers' (a positive integer)."));
+    let options = parquet_ops::SortOptions {
+        num_workers,
+        ..Default::default()
+    };
 
     let timer = Instant::now();
 
-    parquet_ops::sort(path_1, sorted_path, "id");
+    parquet_ops::sort_with_options(path_1, sorted_path, "id", &options);
 
     let elapsed = timer.elapsed();
 
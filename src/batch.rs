@@ -0,0 +1,14 @@
+//! Counterparts of 'RowIterExt' and 'RowWriteBuffer' based on Arrow 'RecordBatch'es, together with a sort and a merge
+//! that operate on batches. The columnar batches avoid the allocation of a 'Row' (and a 'Field' per value) for each row,
+//! which dominates the processing of wide files via the record-API.
+mod batch_reader;
+pub use batch_reader::RecordBatchIter;
+mod batch_writer;
+pub use batch_writer::BatchWriteBuffer;
+mod batch_merge;
+pub use batch_merge::{merge_batches_sorted, merge_sorted_batches};
+mod batch_sort;
+pub use batch_sort::{sort_batch, sort_batches};
+
+/// The number of rows per batch that is read or produced by the batch-operations.
+pub const BATCH_SIZE: usize = 8192;
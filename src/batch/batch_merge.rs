@@ -0,0 +1,269 @@
+use super::batch_sort::{key_column, without_metadata};
+use super::{BatchWriteBuffer, RecordBatchIter, BATCH_SIZE};
+use crate::{merge::MergeHeap, progress::Progress, sort_metadata::SortMetadata};
+use arrow::{
+    array::Array,
+    compute::interleave,
+    datatypes::{Schema, SchemaRef},
+    record_batch::RecordBatch,
+    row::{RowConverter, Rows, SortField},
+};
+use std::cmp::Ordering;
+
+/// The current batch of an input of the merge, with its keys in the row-format of arrow, such that the keys of
+/// different batches can be compared directly.
+struct Cursor<I> {
+    input: I,
+    batch: RecordBatch,
+    keys: Rows,
+    pos: usize,
+    /// The position of 'batch' in the batches that are referenced by the output that is being assembled.
+    pinned: usize,
+}
+
+/// Collects the rows of the output as (batch, row) references, which are copied to an output batch by 'interleave'.
+struct OutputBuilder {
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+    indices: Vec<(usize, usize)>,
+}
+
+impl OutputBuilder {
+    fn pin(&mut self, batch: &RecordBatch) -> usize {
+        self.batches.push(batch.clone());
+        self.batches.len() - 1
+    }
+
+    /// Build the output batch, and only keep the current batches of the inputs pinned.
+    fn build<I>(&mut self, cursors: &mut [Option<Cursor<I>>]) -> RecordBatch {
+        let columns = (0..self.schema.fields().len())
+            .map(|col| {
+                let arrays: Vec<&dyn Array> = self.batches.iter().map(|batch| batch.column(col).as_ref()).collect();
+                interleave(&arrays, &self.indices).unwrap()
+            })
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns).unwrap();
+
+        self.batches.clear();
+        self.indices.clear();
+        cursors.iter_mut().flatten().for_each(|cursor| {
+            self.batches.push(cursor.batch.clone());
+            cursor.pinned = self.batches.len() - 1;
+        });
+        batch
+    }
+}
+
+/// Load the next non-empty batch of 'input' with its keys in column 'col'.
+fn next_batch<I>(input: &mut I, col: usize, converter: &mut RowConverter) -> Option<(RecordBatch, Rows)>
+where
+    I: Iterator<Item = RecordBatch>,
+{
+    let batch = input.find(|batch| batch.num_rows() > 0)?;
+    let keys = converter.convert_columns(&[batch.column(col).clone()]).unwrap();
+    Some((batch, keys))
+}
+
+fn check_columns(batch: &RecordBatch, schema: &Schema) {
+    assert_eq!(batch.schema().fields(), schema.fields(), "All inputs of the merge should have the same columns");
+}
+
+/// Merge the 'inputs', of which the batches are sorted ascending on column 'key', and pass the merged batches of (at most)
+/// 'batch_size' rows to 'batch_processor'. All inputs should have the columns of 'schema'. On equal keys the rows of
+/// the input with the lowest index go first, so the merge is stable.
+pub fn merge_sorted_batches<I, P>(
+    inputs: Vec<I>,
+    schema: SchemaRef,
+    key: &str,
+    batch_size: usize,
+    mut batch_processor: P,
+) where
+    I: Iterator<Item = RecordBatch>,
+    P: FnMut(RecordBatch),
+{
+    let col = key_column(&schema, key);
+    let mut converter = RowConverter::new(vec![SortField::new(schema.field(col).data_type().clone())])
+        .unwrap_or_else(|err| panic!("Column '{key}' can not be merged: {err}"));
+
+    let mut output = OutputBuilder {
+        schema: without_metadata(&schema),
+        batches: Vec::new(),
+        indices: Vec::with_capacity(batch_size),
+    };
+    let mut cursors: Vec<Option<Cursor<I>>> = inputs
+        .into_iter()
+        .map(|mut input| {
+            next_batch(&mut input, col, &mut converter).map(|(batch, keys)| {
+                check_columns(&batch, &schema);
+                let pinned = output.pin(&batch);
+                Cursor {
+                    input,
+                    batch,
+                    keys,
+                    pos: 0,
+                    pinned,
+                }
+            })
+        })
+        .collect();
+
+    let mut heap = {
+        let precedes = |i: usize, j: usize| cursor_precedes(&cursors, i, j);
+        MergeHeap::new((0..cursors.len()).filter(|i| cursors[*i].is_some()).collect(), &precedes)
+    };
+    while let Some(top) = heap.peek() {
+        let cursor = cursors[top].as_mut().unwrap();
+        output.indices.push((cursor.pinned, cursor.pos));
+        cursor.pos += 1;
+        if cursor.pos == cursor.batch.num_rows() {
+            match next_batch(&mut cursor.input, col, &mut converter) {
+                Some((batch, keys)) => {
+                    check_columns(&batch, &schema);
+                    cursor.pinned = output.pin(&batch);
+                    cursor.batch = batch;
+                    cursor.keys = keys;
+                    cursor.pos = 0;
+                }
+                None => cursors[top] = None,
+            }
+        }
+
+        let precedes = |i: usize, j: usize| cursor_precedes(&cursors, i, j);
+        if cursors[top].is_none() {
+            heap.pop(&precedes);
+        } else {
+            heap.sift_down(0, &precedes);
+        }
+        if output.indices.len() == batch_size {
+            batch_processor(output.build(&mut cursors));
+        }
+    }
+    if !output.indices.is_empty() {
+        batch_processor(output.build(&mut cursors));
+    }
+}
+
+/// The strict order of the inputs 'i' and 'j' on their current key, where ties are broken on the index of the input.
+fn cursor_precedes<I>(cursors: &[Option<Cursor<I>>], i: usize, j: usize) -> bool {
+    let (left, right) = (cursors[i].as_ref().unwrap(), cursors[j].as_ref().unwrap());
+    match left.keys.row(left.pos).cmp(&right.keys.row(right.pos)) {
+        Ordering::Equal => i < j,
+        ordering => ordering == Ordering::Less,
+    }
+}
+
+/// Merge the files 'paths', which are sorted ascending on 'key', to 'merged_path' in row-groups of 'group_size' rows,
+/// which is marked as sorted on 'key'.
+pub fn merge_batches_sorted(paths: Vec<&str>, merged_path: &str, key: &str, group_size: usize, progress: Progress) {
+    assert!(!paths.is_empty(), "Nothing to merge");
+
+    let inputs: Vec<RecordBatchIter> = paths
+        .iter()
+        .map(|path| RecordBatchIter::new(path, BATCH_SIZE).with_progress(progress.clone()))
+        .collect();
+    let schema = without_metadata(&inputs[0].schema());
+    let sort_metadata = SortMetadata {
+        key: key.to_owned(),
+        column: key_column(&schema, key),
+        descending: false,
+        algorithm: "merge".to_owned(),
+    };
+    progress.phase(merged_path, "merge-batches");
+    let mut writer = BatchWriteBuffer::new_with_progress(
+        merged_path,
+        schema.clone(),
+        group_size,
+        Some(sort_metadata.to_key_value_metadata()),
+        progress,
+    );
+    merge_sorted_batches(inputs, schema, key, BATCH_SIZE, |batch| writer.append_batch(&batch));
+    writer.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_batches_sorted, merge_sorted_batches};
+    use crate::batch::{sort_batches, BatchWriteBuffer, RecordBatchIter};
+    use crate::progress::Progress;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use arrow::{
+        array::{Array, ArrayRef, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use std::sync::Arc;
+
+    fn write_batch(path: &str, ids: Vec<i64>) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("account", DataType::Utf8, false),
+        ]));
+        let accounts: Vec<String> = ids.iter().map(|id| format!("account-{id}")).collect();
+        let columns: Vec<ArrayRef> = vec![Arc::new(Int64Array::from(ids)), Arc::new(StringArray::from(accounts))];
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let mut writer = BatchWriteBuffer::new(path, schema, 4);
+        writer.append_batch(&batch);
+        writer.close();
+    }
+
+    fn read_ids(path: &str) -> Vec<i64> {
+        RecordBatchIter::new(path, 3)
+            .flat_map(|batch| {
+                let ids = batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap().clone();
+                (0..ids.len()).map(move |i| ids.value(i)).collect::<Vec<i64>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_and_merge_batches() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let (unsorted, sorted, other, merged) = (
+            scratch.path("unsorted.parquet"),
+            scratch.path("sorted.parquet"),
+            scratch.path("other.parquet"),
+            scratch.path("merged.parquet"),
+        );
+        let (unsorted, sorted, other, merged) = (unsorted.as_str(), sorted.as_str(), other.as_str(), merged.as_str());
+        write_batch(unsorted, vec![7, 3, 9, 1, 5, 3]);
+        sort_batches(unsorted, sorted, "id", 4, Progress::default());
+        assert_eq!(read_ids(sorted), vec![1, 3, 3, 5, 7, 9]);
+
+        write_batch(other, vec![2, 3, 8, 10]);
+        merge_batches_sorted(vec![sorted, other], merged, "id", 4, Progress::default());
+        assert_eq!(read_ids(merged), vec![1, 2, 3, 3, 3, 5, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_merge_sorted_batches_is_stable() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("input", DataType::Int64, false),
+        ]));
+        let batch = |input: i64, ids: Vec<i64>| {
+            let inputs = Int64Array::from(vec![input; ids.len()]);
+            let columns: Vec<ArrayRef> = vec![Arc::new(Int64Array::from(ids)), Arc::new(inputs)];
+            RecordBatch::try_new(schema.clone(), columns).unwrap()
+        };
+        // empty batches within an input, an input of only empty batches, and an input without batches.
+        let inputs = vec![
+            vec![batch(0, vec![1, 3]), batch(0, vec![]), batch(0, vec![3, 5])],
+            vec![batch(1, vec![]), batch(1, vec![])],
+            vec![],
+            vec![batch(3, vec![]), batch(3, vec![0, 3, 5])],
+        ];
+
+        let mut merged: Vec<(i64, i64)> = Vec::new();
+        let mut batch_sizes = Vec::new();
+        let inputs = inputs.into_iter().map(|input| input.into_iter()).collect();
+        merge_sorted_batches(inputs, schema.clone(), "id", 3, |batch| {
+            let column = |col: usize| batch.column(col).as_any().downcast_ref::<Int64Array>().unwrap().clone();
+            let (ids, inputs) = (column(0), column(1));
+            merged.extend((0..ids.len()).map(|i| (ids.value(i), inputs.value(i))));
+            batch_sizes.push(batch.num_rows());
+        });
+        // on equal ids the rows of the input with the lowest index go first.
+        assert_eq!(merged, vec![(0, 3), (1, 0), (3, 0), (3, 0), (3, 3), (5, 0), (5, 3)]);
+        assert_eq!(batch_sizes, vec![3, 3, 1]);
+    }
+}
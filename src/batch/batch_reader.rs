@@ -0,0 +1,107 @@
+use crate::progress::Progress;
+use arrow::{
+    datatypes::SchemaRef,
+    record_batch::{RecordBatch, RecordBatchReader},
+};
+use parquet::{
+    arrow::{
+        arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+        ProjectionMask,
+    },
+    file::reader::ChunkReader,
+};
+use s3_file::S3Reader;
+use std::fs::File;
+
+/// Iterator over the 'RecordBatch'es of a Parquet-file or S3-object, which is the columnar counterpart of 'RowIterExt'.
+/// The paths follow the conventions of 'get_parquet_reader', so a path 's3:<bucket>:<object_name>' refers to an S3-object.
+pub struct RecordBatchIter {
+    path: String,
+    reader: ParquetRecordBatchReader,
+    progress: Option<Progress>,
+}
+
+/// Build the batch-reader, where the 'columns' are the names of the top-level columns that are read.
+fn build_reader<T>(chunk_reader: T, path: &str, columns: Option<&[&str]>, batch_size: usize) -> ParquetRecordBatchReader
+where
+    T: ChunkReader + 'static,
+{
+    let builder = ParquetRecordBatchReaderBuilder::try_new(chunk_reader)
+        .unwrap_or_else(|err| panic!("Failed to open '{path}' for reading batches: {err}"));
+    let builder = match columns {
+        Some(columns) => {
+            let schema_descr = builder.parquet_schema();
+            let roots: Vec<usize> = columns
+                .iter()
+                .map(|name| {
+                    schema_descr
+                        .root_schema()
+                        .get_fields()
+                        .iter()
+                        .position(|field| field.name() == *name)
+                        .unwrap_or_else(|| panic!("Column '{name}' does not occur in '{path}'"))
+                })
+                .collect();
+            let mask = ProjectionMask::roots(schema_descr, roots);
+            builder.with_projection(mask)
+        }
+        None => builder,
+    };
+    builder
+        .with_batch_size(batch_size)
+        .build()
+        .unwrap_or_else(|err| panic!("Failed to read batches from '{path}': {err}"))
+}
+
+impl RecordBatchIter {
+    pub fn new(path: &str, batch_size: usize) -> Self {
+        Self::new_projected(path, None, batch_size)
+    }
+
+    /// Create a RecordBatchIter that only reads the top-level 'columns'. The columns keep the order of the file.
+    pub fn new_projected(path: &str, columns: Option<&[&str]>, batch_size: usize) -> Self {
+        let reader = match path.split(':').next().unwrap() {
+            prefix if path.len() == prefix.len() => {
+                let file = File::open(path).unwrap_or_else(|err| panic!("Failed to open file {path}: {err}"));
+                build_reader(file, path, columns, batch_size)
+            }
+            "s3" => {
+                let parts: Vec<&str> = path.split(':').collect();
+                assert_eq!(parts.len(), 3, "Path should have format \"s3:<bucket>:<object_name>\".");
+                let chunk_reader = S3Reader::new(parts[1].to_owned(), parts[2].to_owned(), 10_000 * 1024);
+                build_reader(chunk_reader, path, columns, batch_size)
+            }
+            prefix => panic!("RecordBatchIter not implemented for prefix {prefix} of path {path}"),
+        };
+        RecordBatchIter {
+            path: path.to_owned(),
+            reader,
+            progress: None,
+        }
+    }
+
+    /// Report the rows of each batch that is read to 'progress'.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.reader.schema()
+    }
+}
+
+impl Iterator for RecordBatchIter {
+    type Item = RecordBatch;
+
+    fn next(&mut self) -> Option<RecordBatch> {
+        let batch = self
+            .reader
+            .next()?
+            .unwrap_or_else(|err| panic!("Reading a batch of '{}' failed: {err}", self.path));
+        if let Some(progress) = &self.progress {
+            progress.rows_read(&self.path, batch.num_rows() as u64);
+        }
+        Some(batch)
+    }
+}
@@ -0,0 +1,159 @@
+use super::{merge_sorted_batches, BatchWriteBuffer, RecordBatchIter, BATCH_SIZE};
+use crate::{
+    progress::Progress,
+    scratch::{ScratchLocation, ScratchSpace},
+    sort_metadata::SortMetadata,
+};
+use arrow::{
+    array::UInt32Array,
+    compute::{concat_batches, take},
+    datatypes::{Schema, SchemaRef},
+    record_batch::RecordBatch,
+    row::{RowConverter, SortField},
+};
+use std::sync::Arc;
+
+/// The number of rows that is sorted in memory as a single run.
+const RUN_ROWS: usize = 1_000_000;
+
+/// The position of column 'key' in 'schema'.
+pub(super) fn key_column(schema: &Schema, key: &str) -> usize {
+    schema
+        .index_of(key)
+        .unwrap_or_else(|_| panic!("Failed to find a column with name: '{key}'"))
+}
+
+/// Copy of 'schema' without its metadata. The schema of a batch that is read contains the key-value metadata of the file
+/// (such as its sort-metadata), which should not be copied to the output.
+pub(super) fn without_metadata(schema: &Schema) -> SchemaRef {
+    Arc::new(Schema::new(schema.fields().clone()))
+}
+
+/// Sort 'batch' ascending on column 'key'. The sort is stable and nulls sort first, as in the row-based sort.
+pub fn sort_batch(batch: &RecordBatch, key: &str) -> RecordBatch {
+    let column = batch.column(key_column(&batch.schema(), key));
+    let mut converter = RowConverter::new(vec![SortField::new(column.data_type().clone())])
+        .unwrap_or_else(|err| panic!("Column '{key}' can not be sorted: {err}"));
+    let keys = converter.convert_columns(&[column.clone()]).unwrap();
+
+    let mut indices: Vec<u32> = (0..batch.num_rows() as u32).collect();
+    indices.sort_by(|left, right| keys.row(*left as usize).cmp(&keys.row(*right as usize)));
+    let indices = UInt32Array::from(indices);
+
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|column| take(column.as_ref(), &indices, None).unwrap())
+        .collect();
+    RecordBatch::try_new(batch.schema(), columns).unwrap()
+}
+
+/// Sort the 'input' on column 'key' in runs of at least 'run_rows' rows, where each run but the last is spilled to a
+/// file in the 'scratch' space. Returns the runs in input order, such that a stable merge of the runs is a stable sort.
+fn sorted_runs<I>(
+    input: I,
+    schema: &SchemaRef,
+    key: &str,
+    run_rows: usize,
+    scratch: &mut ScratchSpace,
+) -> Vec<Box<dyn Iterator<Item = RecordBatch>>>
+where
+    I: Iterator<Item = RecordBatch>,
+{
+    let sort_run = |batches: &[RecordBatch]| {
+        let run = concat_batches(&batches[0].schema(), batches).unwrap();
+        let run = RecordBatch::try_new(schema.clone(), run.columns().to_vec()).unwrap();
+        sort_batch(&run, key)
+    };
+    let mut runs: Vec<Box<dyn Iterator<Item = RecordBatch>>> = Vec::new();
+    let mut pending: Vec<RecordBatch> = Vec::new();
+    for batch in input {
+        pending.push(batch);
+        if pending.iter().map(|batch| batch.num_rows()).sum::<usize>() >= run_rows {
+            let run_path = scratch.path(&format!("run-{}.parquet", runs.len()));
+            let mut run_writer = BatchWriteBuffer::new(&run_path, schema.clone(), BATCH_SIZE);
+            run_writer.append_batch(&sort_run(&pending));
+            run_writer.close();
+            runs.push(Box::new(RecordBatchIter::new(&run_path, BATCH_SIZE)));
+            pending.clear();
+        }
+    }
+    if !pending.is_empty() {
+        runs.push(Box::new(vec![sort_run(&pending)].into_iter()));
+    }
+    runs
+}
+
+/// Sort the input on column 'sort_field_name' and write it to 'sorted_path' in row-groups of 'group_size' rows, which
+/// is marked as sorted on that column. The input is sorted in runs of about a million rows, after which the runs are
+/// merged. Each run but the last is spilled to a local scratch space, so only the last run and a batch of each spilled
+/// run are in memory at once.
+pub fn sort_batches(input_path: &str, sorted_path: &str, sort_field_name: &str, group_size: usize, progress: Progress) {
+    let input = RecordBatchIter::new(input_path, BATCH_SIZE).with_progress(progress.clone());
+    let schema = without_metadata(&input.schema());
+    let sort_metadata = SortMetadata {
+        key: sort_field_name.to_owned(),
+        column: key_column(&schema, sort_field_name),
+        descending: false,
+        algorithm: "arrow".to_owned(),
+    };
+
+    progress.phase(sorted_path, "sort-batches");
+    // the scratch space removes the spilled runs when it is dropped.
+    let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "sort-batches");
+    let runs = sorted_runs(input, &schema, sort_field_name, RUN_ROWS, &mut scratch);
+
+    progress.phase(sorted_path, "merge-batches");
+    let mut writer = BatchWriteBuffer::new_with_progress(
+        sorted_path,
+        schema.clone(),
+        group_size,
+        Some(sort_metadata.to_key_value_metadata()),
+        progress,
+    );
+    merge_sorted_batches(runs, schema, sort_field_name, BATCH_SIZE, |batch| writer.append_batch(&batch));
+    writer.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sorted_runs;
+    use crate::batch::merge_sorted_batches;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use arrow::{
+        array::{Array, ArrayRef, Int64Array},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sort_spilled_runs() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("seq", DataType::Int64, false),
+        ]));
+        // 17 batches of (at most) 6 rows
+        let batches = (0..100).step_by(6).map(|start: i64| {
+            let seqs: Vec<i64> = (start..(start + 6).min(100)).collect();
+            let ids: Vec<i64> = seqs.iter().map(|seq| seq * 37 % 10).collect();
+            let columns: Vec<ArrayRef> = vec![Arc::new(Int64Array::from(ids)), Arc::new(Int64Array::from(seqs))];
+            RecordBatch::try_new(schema.clone(), columns).unwrap()
+        });
+
+        // runs of 3 batches, so 5 runs are spilled and the last 2 batches form a run in memory.
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let runs = sorted_runs(batches, &schema, "id", 16, &mut scratch);
+        assert_eq!(runs.len(), 6);
+
+        let mut rows: Vec<(i64, i64)> = Vec::new();
+        merge_sorted_batches(runs, schema.clone(), "id", 7, |batch| {
+            let column = |col: usize| batch.column(col).as_any().downcast_ref::<Int64Array>().unwrap().clone();
+            let (ids, seqs) = (column(0), column(1));
+            rows.extend((0..ids.len()).map(|i| (ids.value(i), seqs.value(i))));
+        });
+        assert_eq!(rows.len(), 100);
+        // sorted on the id, and stable: equal ids keep their input order.
+        assert!(rows.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::progress::Progress;
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::{metadata::KeyValue, properties::WriterProperties},
+};
+use s3_file::S3Writer;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+};
+
+enum ArrowFileWriter {
+    File(ArrowWriter<BufWriter<fs::File>>),
+    S3(ArrowWriter<S3Writer>),
+}
+
+/// Writes 'RecordBatch'es to a Parquet-file or S3-object, which is the columnar counterpart of 'RowWriteBuffer'.
+/// The batches are buffered by the writer until a row-group of 'group_size' rows is complete.
+pub struct BatchWriteBuffer {
+    path: String,
+    writer: Option<ArrowFileWriter>,
+    progress: Progress,
+}
+
+fn create_writer<W: Write>(writer: W, path: &str, schema: SchemaRef, props: WriterProperties) -> ArrowWriter<W> {
+    ArrowWriter::try_new(writer, schema, Some(props)).unwrap_or_else(|err| panic!("Failed to create '{path}': {err}"))
+}
+
+impl BatchWriteBuffer {
+    pub fn new(path: &str, schema: SchemaRef, group_size: usize) -> Self {
+        Self::new_with_metadata(path, schema, group_size, None)
+    }
+
+    /// Create a BatchWriteBuffer that stores the 'key_value_metadata' in the footer of the file.
+    pub fn new_with_metadata(
+        path: &str,
+        schema: SchemaRef,
+        group_size: usize,
+        key_value_metadata: Option<Vec<KeyValue>>,
+    ) -> Self {
+        Self::new_with_progress(path, schema, group_size, key_value_metadata, Progress::default())
+    }
+
+    /// Create a BatchWriteBuffer that reports the rows written to 'progress'.
+    pub fn new_with_progress(
+        path: &str,
+        schema: SchemaRef,
+        group_size: usize,
+        key_value_metadata: Option<Vec<KeyValue>>,
+        progress: Progress,
+    ) -> Self {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(group_size)
+            .set_key_value_metadata(key_value_metadata)
+            .build();
+
+        let parts: Vec<&str> = path.split(':').collect();
+        let writer = match parts.len() {
+            1 => {
+                let file = fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)
+                    .unwrap_or_else(|err| panic!("Failed to create '{path}': {err}"));
+                ArrowFileWriter::File(create_writer(BufWriter::new(file), path, schema, props))
+            }
+            3 => {
+                assert_eq!(parts[0], "s3");
+                let s3_writer = S3Writer::new(parts[1].to_owned(), parts[2].to_owned(), 10_000_000);
+                ArrowFileWriter::S3(create_writer(s3_writer, path, schema, props))
+            }
+            _ => panic!("File-path should have no colon (:) or S3-path should have format \"s3:<bucket>:<object_name>\"."),
+        };
+
+        BatchWriteBuffer {
+            path: path.to_owned(),
+            writer: Some(writer),
+            progress,
+        }
+    }
+
+    pub fn append_batch(&mut self, batch: &RecordBatch) {
+        let result = match self.writer.as_mut().expect("The BatchWriteBuffer is already closed") {
+            ArrowFileWriter::File(writer) => writer.write(batch),
+            ArrowFileWriter::S3(writer) => writer.write(batch),
+        };
+        result.unwrap_or_else(|err| panic!("Writing a batch to '{}' failed: {err}", self.path));
+        self.progress.rows_written(&self.path, batch.num_rows() as u64);
+    }
+
+    /// Flush the last row-group and write the footer.
+    pub fn close(&mut self) {
+        let result = match self.writer.take() {
+            Some(ArrowFileWriter::File(writer)) => writer.close(),
+            Some(ArrowFileWriter::S3(writer)) => writer.close(),
+            None => return,
+        };
+        if let Err(err) = result {
+            panic!("Closing '{}' failed: {err}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchWriteBuffer;
+    use crate::batch::RecordBatchIter;
+    use crate::parquet_reader::get_parquet_reader;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use arrow::{
+        array::{ArrayRef, Float64Array, Int64Array, StringArray},
+        compute::concat_batches,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_batch_write_buffer_roundtrip() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = scratch.path("batches.parquet");
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("account", DataType::Utf8, true),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batches: Vec<RecordBatch> = [0..6, 6..10]
+            .into_iter()
+            .map(|ids| {
+                let accounts: Vec<Option<String>> =
+                    ids.clone().map(|id| (id % 3 != 0).then(|| format!("account-{id}"))).collect();
+                let amounts: Vec<f64> = ids.clone().map(|id| id as f64 / 4.0).collect();
+                let columns: Vec<ArrayRef> = vec![
+                    Arc::new(Int64Array::from_iter_values(ids)),
+                    Arc::new(StringArray::from(accounts)),
+                    Arc::new(Float64Array::from(amounts)),
+                ];
+                RecordBatch::try_new(schema.clone(), columns).unwrap()
+            })
+            .collect();
+
+        let mut writer = BatchWriteBuffer::new(&path, schema.clone(), 4);
+        batches.iter().for_each(|batch| writer.append_batch(batch));
+        writer.close();
+
+        // the 10 rows are written in row-groups of 4 rows, independent of the size of the batches.
+        assert_eq!(get_parquet_reader(&path).metadata().num_row_groups(), 3);
+        let read: Vec<RecordBatch> = RecordBatchIter::new(&path, 3).collect();
+        let read = concat_batches(&read[0].schema(), &read).unwrap();
+        let written = concat_batches(&schema, &batches).unwrap();
+        assert_eq!(read.columns(), written.columns());
+    }
+}
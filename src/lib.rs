@@ -1,29 +1,61 @@
 pub use self::{
-    merge::{merge_parquet, merge_parquet_fake},
-    metadata::{find_field, get_parquet_metadata, show_parquet_metadata},
+    lookup::{lookup, lookup_range},
+    merge::{
+        compact, compact_with_options, diff_sorted, diff_sorted_with, merge_join, merge_parquet, merge_parquet_by_key,
+        merge_parquet_fake, merge_parquet_sorted, merge_parquet_with_options, ChangeKind, CompactionOptions, DiffStats,
+        JoinType, KeepVersion, MergeOptions, RowChange, SchemaStrategy,
+    },
+    metadata::{find_column, find_field, get_message_type, get_parquet_metadata, show_parquet_metadata},
     object_size::get_object_size,
+    predicate::{col, Column, CompareOp, Literal, Predicate},
+    progress::{ConsoleObserver, Progress, ProgressObserver, SilentObserver},
+    projection::OutputColumn,
     rowiterext::ttest::read_parquet_rowiter,
-    rowiterext::{get_parquet_iter, read_row_sample, read_rows, read_rows_stepped},
+    rowiterext::{
+        get_parquet_iter, get_parquet_iter_where, read_row_sample, read_rows, read_rows_stepped, read_rows_where, RowIterExt,
+    },
     rowwritebuffer::RowWriteBuffer,
-    sort::sort,
+    scratch::ScratchLocation,
+    sort::{
+        bucket_sort, sort, sort_by, sort_top_n, sort_with_key, sort_with_options, DedupOptions, KeepRow,
+        SortMultistageParquet, SortOptions,
+    },
+    sort_metadata::{get_sort_metadata, SortMetadata},
     testdata_writer::write_parquet,
     ttypes::{ACCOUNT_ONLY_TYPE, ID_ONLY_TYPE, MESSAGE_TYPE},
 };
 
+#[cfg(feature = "arrow")]
+pub use self::batch::{
+    merge_batches_sorted, merge_sorted_batches, sort_batch, sort_batches, BatchWriteBuffer, RecordBatchIter, BATCH_SIZE,
+};
+
+#[cfg(feature = "arrow")]
+mod batch;
+
+mod lookup;
 mod merge;
 mod metadata;
+mod object_remove;
 mod object_size;
 mod parquet_reader;
 mod parquet_writer;
+mod predicate;
+mod progress;
+mod projection;
 mod rowiterext;
+mod row_compare;
 mod rowwritebuffer;
+mod scratch;
 // test stuff
 mod ttypes;
 //mod test_writer;
 mod sort;
+mod sort_metadata;
+mod statistics;
 
 // TODO: to be dropped. Still in place for nested types??
 //mod legacy_writer;
 mod testdata_writer;
 
-const REPORT_APPEND_STEP: i64 = 100; //10_000  // 1 in REPORT_APPEND_STEP rows is reported on the console.
+const REPORT_ROWS_STEP: u64 = 10_000; // the rows read are reported to the ProgressObserver in batches of REPORT_ROWS_STEP rows.
@@ -0,0 +1,136 @@
+use crate::{
+    find_column, find_field,
+    parquet_reader::{get_parquet_reader, RowGroupPredicate},
+    predicate::{checked_value, col, Literal},
+    row_compare::get_field,
+    rowiterext::{get_parquet_iter_filtered, get_parquet_iter_where},
+    sort_metadata::get_sort_metadata,
+    statistics::{row_group_min_max, StatValue},
+};
+use parquet::{
+    file::metadata::RowGroupMetaData,
+    record::{Field, Row},
+};
+use std::{cmp::Ordering, ops::Range, sync::Arc};
+
+/// Get the rows of 'path' where column 'key_col' equals 'value' (see 'lookup_range').
+pub fn lookup<V: Literal>(path: &str, key_col: &str, value: V) -> Vec<Row> {
+    let value = value.into_field();
+    lookup_fields(path, key_col, value.clone(), value)
+}
+
+/// Get the rows of 'path' where column 'key_col' is in the range [low, high], so including both bounds.
+/// If the file is sorted ascending on 'key_col' (according to its sort-metadata) the row-groups that can contain the range
+/// are found by a binary search on their min/max statistics, and only these row-groups are read up to the first key
+/// beyond 'high'. Otherwise all row-groups are pruned on their statistics and the remaining rows are filtered.
+/// The record-API reads complete column-chunks, so a page index (if present) does not reduce the data that is read.
+pub fn lookup_range<V: Literal>(path: &str, key_col: &str, low: V, high: V) -> Vec<Row> {
+    lookup_fields(path, key_col, low.into_field(), high.into_field())
+}
+
+fn lookup_fields(path: &str, key_col: &str, low: Field, high: Field) -> Vec<Row> {
+    let is_sorted = get_sort_metadata(path).map_or(false, |sm| sm.key == key_col && !sm.descending);
+    if !is_sorted {
+        let predicate = col(key_col).between(low, high);
+        let (rows, _) =
+            get_parquet_iter_where(path, None, &predicate).unwrap_or_else(|| panic!("Failed to read '{path}'"));
+        return rows.collect();
+    }
+
+    let metadata = get_parquet_reader(path).metadata();
+    let schema = Arc::new(metadata.file_metadata().schema().clone());
+    // the statistics are selected by the leaf-index of the key, the fields of the rows by its position.
+    let (key_leaf, _) = find_field(Arc::clone(&schema), key_col);
+    let (key_pos, key_type) = find_column(schema, key_col);
+    let (low, high) = (checked_value(&key_type, &low), checked_value(&key_type, &high));
+
+    let row_groups = row_group_range(metadata.row_groups(), key_leaf, &low, &high);
+    if row_groups.is_empty() {
+        return Vec::new();
+    }
+    let predicate: RowGroupPredicate = Box::new(move |_: &RowGroupMetaData, idx: usize| row_groups.contains(&idx));
+    let (rows, _) =
+        get_parquet_iter_filtered(path, None, Some(predicate)).unwrap_or_else(|| panic!("Failed to read '{path}'"));
+
+    // a null key sorts first, so it is smaller than 'low'
+    let compare_key = |row: &Row, bound: &StatValue| {
+        StatValue::from_field(get_field(row, key_pos))
+            .and_then(|key| key.compare(bound))
+            .unwrap_or(Ordering::Less)
+    };
+    rows.skip_while(|row| compare_key(row, &low) == Ordering::Less)
+        .take_while(|row| compare_key(row, &high) != Ordering::Greater)
+        .collect()
+}
+
+/// The row-groups of a file that is sorted on column 'col' that can contain keys in [low, high]. If a row-group lacks
+/// statistics for 'col' all row-groups are returned.
+fn row_group_range(row_groups: &[RowGroupMetaData], col: usize, low: &StatValue, high: &StatValue) -> Range<usize> {
+    let min_max: Option<Vec<(StatValue, StatValue)>> =
+        row_groups.iter().map(|row_group| row_group_min_max(row_group, col)).collect();
+    let Some(min_max) = min_max else {
+        return 0..row_groups.len();
+    };
+    let first = min_max.partition_point(|(_, max)| max.compare(low) == Some(Ordering::Less));
+    let end = min_max.partition_point(|(min, _)| min.compare(high) != Some(Ordering::Greater));
+    first..end.max(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lookup, lookup_range};
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use crate::sort_metadata::SortMetadata;
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    fn write_accounts(path: &str, sorted: bool) {
+        let schema =
+            Arc::new(parse_message_type("message schema { REQUIRED BINARY account (UTF8); REQUIRED INT64 id; }").unwrap());
+        let sort_metadata = SortMetadata {
+            key: "account".to_owned(),
+            column: 0,
+            descending: false,
+            algorithm: "simple".to_owned(),
+        };
+        let key_value_metadata = sorted.then(|| sort_metadata.to_key_value_metadata());
+        let mut row_writer = RowWriteBuffer::new_with_metadata(path, schema, 10, key_value_metadata).unwrap();
+        // 4 rows per account, so the accounts span the boundaries of the row-groups
+        (0..50).for_each(|id| {
+            row_writer.append_row(create_row(vec![
+                ("account".to_owned(), Field::Str(format!("account-{:02}", id / 4))),
+                ("id".to_owned(), Field::Long(id)),
+            ]))
+        });
+        row_writer.close();
+    }
+
+    fn ids(rows: Vec<Row>) -> Vec<i64> {
+        rows.iter().map(|row| row.get_long(1).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        for (path, sorted) in [(scratch.path("sorted.parquet"), true), (scratch.path("unsorted.parquet"), false)] {
+            let path = path.as_str();
+            write_accounts(path, sorted);
+            assert_eq!(ids(lookup(path, "account", "account-03")), vec![12, 13, 14, 15]);
+            assert_eq!(ids(lookup_range(path, "account", "account-01", "account-02")), (4..12).collect::<Vec<i64>>());
+            assert!(lookup(path, "account", "account-99").is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Column 'account' of type BYTE_ARRAY can not be compared with Long(3)")]
+    fn test_lookup_rejects_other_type() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = scratch.path("sorted.parquet");
+        write_accounts(&path, true);
+        lookup(&path, "account", 3_i64);
+    }
+}
@@ -1,10 +1,79 @@
-use parquet::record::{Row, RowAccessor};
-use std::sync::Arc;
-
+use parquet::{record::Row, schema::types::Type};
+use std::{cmp::Ordering, sync::Arc};
+
+use super::metadata::find_column;
+use super::parquet_reader::get_parquet_reader;
+use super::progress::Progress;
+use super::projection::{ColumnProjection, OutputColumn};
+use super::row_compare::{compare_columns, compare_fields, get_field};
 use super::rowiterext::RowIterExt;
 use super::rowwritebuffer::RowWriteBuffer;
+use super::sort_metadata::{get_sort_metadata, SortMetadata};
+
+mod compact_files;
+pub use compact_files::{compact, compact_with_options};
+mod compaction;
+use compaction::Compactor;
+pub use compaction::{CompactionOptions, KeepVersion};
+mod diff;
+pub use diff::{diff_sorted, diff_sorted_with, ChangeKind, DiffStats, RowChange};
+mod join;
+pub use join::{merge_join, JoinType};
+mod reconcile;
+use reconcile::{reconcile_files, InputMapping, Reconciliation};
+pub use reconcile::SchemaStrategy;
+mod verify;
+use verify::{check_row_group_order, InputVerifier};
+
+/// Options that tune the merge. The default corresponds to the behaviour of 'merge_parquet'.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    /// How the schemas of the inputs are reconciled to the schema of the output.
+    pub schema: SchemaStrategy,
+    /// Upsert-compaction: of the rows with an equal key (according to the merge-order) only a single version is kept.
+    /// The inputs should be passed from oldest to newest, for example a base file followed by its delta files.
+    pub compaction: Option<CompactionOptions>,
+    /// Verify that each input is sorted according to the merge-order while merging, and fail with the file, row-group and
+    /// row of the first row that is out of order. For the merges on a named key the order of the row-groups is first checked
+    /// on their statistics, which is cheap and detects most unsorted inputs before merging.
+    pub verify_sorted: bool,
+    /// Number of rows that are read ahead per input by a background thread, such that the merge does not stall on the
+    /// latency of fetching the next row-group of an input (for example of an S3-object). None reads the inputs on the
+    /// merging thread.
+    pub read_ahead: Option<usize>,
+    /// Receives the rows read from each input, the rows written to the output and the row-groups flushed.
+    pub progress: Progress,
+    /// The columns of the output, in output order and optionally renamed. None writes all columns of the reconciled schema.
+    /// The merges on a key only read the output columns and the columns needed by the merge-key and the compaction.
+    pub columns: Option<Vec<OutputColumn>>,
+}
+
+impl MergeOptions {
+    /// The columns that have to be read from the inputs when the merge itself uses the columns 'key'. None reads all columns.
+    fn read_selection(&self, key: &[&str]) -> Option<Vec<String>> {
+        let columns = self.columns.as_ref()?;
+        let compaction_columns = self.compaction.as_ref().map(|compaction| compaction.columns()).unwrap_or_default();
+        let mut selection: Vec<String> = Vec::new();
+        columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .chain(key.iter().copied())
+            .chain(compaction_columns)
+            .for_each(|name| {
+                if !selection.iter().any(|selected| selected == name) {
+                    selection.push(name.to_owned());
+                }
+            });
+        Some(selection)
+    }
 
-use super::REPORT_APPEND_STEP;
+    /// The projection of the merged rows with 'schema' on the output columns.
+    fn output_projection(&self, schema: &Type) -> Option<ColumnProjection> {
+        self.columns
+            .as_ref()
+            .map(|columns| ColumnProjection::new(schema, columns))
+    }
+}
 
 pub fn merge_parquet_fake(_paths: Vec<&str>, merged_path: &str, _smaller: fn(&Row, &Row) -> bool) {
     use crate::ttypes::{get_test_schema, test_parquet_row};
@@ -15,115 +84,357 @@ pub fn merge_parquet_fake(_paths: Vec<&str>, merged_path: &str, _smaller: fn(&Ro
 
     let mut row_writer = RowWriteBuffer::new(merged_path, schema, 10000).unwrap();
 
-    println!("Fill merge_data with fake data (to circumvent the opening of multiple files)");
+    // Fill merge_data with fake data (to circumvent the opening of multiple files)
     (0..num_rows).for_each(|id| row_writer.append_row(test_parquet_row(id, num_extra_columns)));
 
-    println!("Closing the RowWriteBuffer (merge_fake)");
     row_writer.close();
 }
 
 pub fn merge_parquet(paths: Vec<&str>, merged_path: &str, smaller: fn(&Row, &Row) -> bool) {
-    // use crate::barrier::Barrier;
-    // let mut barriers = Barrier::new(0xeeee, 10_000);
+    merge_parquet_with_options(paths, merged_path, smaller, &MergeOptions::default())
+}
 
-    // barriers.add_barrier();
-    // let mut b1: u64 = 1;
+/// Merge the sorted inputs, using the 'options' to select the behaviour of the merge.
+/// The rows passed to 'smaller' have the layout of the reconciled schema (see 'SchemaStrategy'), so all columns are read
+/// also when 'options.columns' only selects some of them.
+pub fn merge_parquet_with_options<F>(paths: Vec<&str>, merged_path: &str, smaller: F, options: &MergeOptions)
+where
+    F: Fn(&Row, &Row) -> bool,
+{
+    let reconciliation = reconcile_files(&paths, options.schema, None);
+    merge_parquet_aux(paths, merged_path, &reconciliation, None, options, smaller)
+}
 
-    // println!("TMP: About to open row_iterators [press NewLine]");
-    // let mut null = Default::default();
-    // std::io::stdin().read_line(&mut null);
+/// Merge files that are sorted on the composite 'key' (the names of the key-columns in order of precedence).
+/// The key is resolved by name in each input, so the inputs may have their columns in a different order. The rows of
+/// all inputs are mapped to the output schema that results from 'options.schema'.
+/// The merged output is marked as sorted on the first column of the 'key', unless that column is not part of 'options.columns'.
+pub fn merge_parquet_by_key(paths: Vec<&str>, merged_path: &str, key: &[&str], options: &MergeOptions) {
+    assert!(!paths.is_empty(), "Nothing to merge");
+    assert!(!key.is_empty(), "The merge-key should contain at least one column");
+
+    // resolving the key in each input fails early (with the name of the column) when an input lacks a key-column
+    paths.iter().for_each(|path| {
+        let schema = Arc::new(get_parquet_reader(path).metadata().file_metadata().schema().clone());
+        key.iter().for_each(|name| {
+            find_column(Arc::clone(&schema), name);
+        });
+    });
+
+    if options.verify_sorted {
+        paths.iter().for_each(|path| check_row_group_order(path, key[0], false));
+    }
 
-    let mut row_iters: Vec<RowIterExt> = paths
+    let reconciliation = reconcile_files(&paths, options.schema, options.read_selection(key).as_deref());
+    let key_cols: Vec<usize> = key
         .iter()
-        .map(|p| RowIterExt::new(p))
-        .filter(|rie| rie.head().is_some())
+        .map(|name| find_column(Arc::clone(&reconciliation.schema), name).0)
         .collect();
 
-    if row_iters.len() < 1 {
-        panic!("Nothing to merge");
-    }
+    let merged_metadata = SortMetadata {
+        key: key[0].to_owned(),
+        column: key_cols[0],
+        descending: false,
+        algorithm: "merge".to_owned(),
+    };
+    let smaller = move |row_1: &Row, row_2: &Row| compare_columns(row_1, row_2, &key_cols) != Ordering::Greater;
+    merge_parquet_aux(paths, merged_path, &reconciliation, Some(merged_metadata), options, smaller)
+}
 
-    // println!("TMP: About to open row_writeBuffer [press NewLine]");
-    // let mut null = Default::default();
-    // std::io::stdin().read_line(&mut null);
+/// Merge files that are sorted by 'sort' (or by an earlier merge) on the sort-key recorded in their metadata.
+/// All inputs should be sorted on the same key. The merged output is marked as sorted on that key as well, unless the
+/// key is not part of 'options.columns'.
+pub fn merge_parquet_sorted(paths: Vec<&str>, merged_path: &str, options: &MergeOptions) {
+    assert!(!paths.is_empty(), "Nothing to merge");
 
-    // barriers.add_barrier();
-    // let mut b2: u64 = 2;
+    let sort_metadata: Vec<SortMetadata> = paths
+        .iter()
+        .map(|path| {
+            get_sort_metadata(path).expect(&format!("File '{path}' is not marked as sorted"))
+        })
+        .collect();
+    let first = &sort_metadata[0];
+    // the position of the key-column may differ, as the inputs are mapped to the output schema
+    if let Some((idx, other)) = sort_metadata
+        .iter()
+        .enumerate()
+        .find(|(_, sm)| sm.key != first.key || sm.descending != first.descending)
+    {
+        panic!(
+            "File '{}' is sorted on {other:?}, while '{}' is sorted on {first:?}",
+            paths[idx], paths[0]
+        );
+    }
 
-    let schema = Arc::new(row_iters[0].schema().clone());
-    let mut row_writer = RowWriteBuffer::new(merged_path, schema, 10000).unwrap();
+    if options.verify_sorted {
+        paths.iter().for_each(|path| check_row_group_order(path, &first.key, first.descending));
+    }
 
-    let mut row_processor = |row: Row| {
-        if row.get_long(0).unwrap() % REPORT_APPEND_STEP == 0 {
-            println!(
-                "Row with id={}, acc={} and amount={}.",
-                row.get_long(0).unwrap(),
-                row.get_string(1).unwrap(),
-                row.get_int(2).unwrap()
-            );
+    let reconciliation = reconcile_files(&paths, options.schema, options.read_selection(&[first.key.as_str()]).as_deref());
+    let (col, _) = find_column(Arc::clone(&reconciliation.schema), &first.key);
+    let descending = first.descending;
+    let smaller = move |row_1: &Row, row_2: &Row| {
+        let ordering = compare_fields(get_field(row_1, col), get_field(row_2, col));
+        if descending {
+            ordering != Ordering::Less
+        } else {
+            ordering != Ordering::Greater
         }
-        row_writer.append_row(row);
     };
 
-    // println!("TMP: About to start the Loop [press NewLine]");
-    // let mut null = Default::default();
-    // std::io::stdin().read_line(&mut null);
+    let merged_metadata = SortMetadata {
+        column: col,
+        algorithm: "merge".to_owned(),
+        ..first.clone()
+    };
+    merge_parquet_aux(paths, merged_path, &reconciliation, Some(merged_metadata), options, smaller)
+}
 
-    // barriers.add_barrier();
-    // let mut b3: u64 = 3;
-    // let mut b4: u64 = 0;
+/// Binary min-heap of input-indices, used to select the input with the smallest head in O(log k) for k inputs.
+/// The order is defined by 'precedes(i, j)', which should be a strict total order on the indices (so ties are broken by index).
+pub(crate) struct MergeHeap {
+    heap: Vec<usize>,
+}
 
-    let _report = true;
+impl MergeHeap {
+    pub(crate) fn new<P>(indices: Vec<usize>, precedes: &P) -> Self
+    where
+        P: Fn(usize, usize) -> bool,
+    {
+        let mut merge_heap = MergeHeap { heap: indices };
+        (0..merge_heap.heap.len() / 2)
+            .rev()
+            .for_each(|pos| merge_heap.sift_down(pos, precedes));
+        merge_heap
+    }
 
-    loop {
-        match row_iters.len() {
-            0 => break, // we are ready
-            1 => {
-                println!("TMP: DRAINING the last one");
-                row_iters[0].drain(&mut row_processor);
-                row_iters.remove(0);
+    pub(crate) fn peek(&self) -> Option<usize> {
+        self.heap.first().copied()
+    }
+
+    /// Restore the heap-order after the key of the element at 'pos' increased.
+    pub(crate) fn sift_down<P>(&mut self, mut pos: usize, precedes: &P)
+    where
+        P: Fn(usize, usize) -> bool,
+    {
+        let len = self.heap.len();
+        loop {
+            let left = 2 * pos + 1;
+            let right = left + 1;
+            let mut first = pos;
+            if left < len && precedes(self.heap[left], self.heap[first]) {
+                first = left;
             }
-            _ => {
-                if let Some((min_pos, _)) = row_iters.iter().enumerate().reduce(|acc, other| {
-                    if smaller(
-                        &acc.1.head().as_ref().unwrap(),
-                        &other.1.head().as_ref().unwrap(),
-                    ) {
-                        acc
-                    } else {
-                        other
-                    }
-                }) {
-                    let (head, ready) = row_iters[min_pos].update_head();
-                    row_processor(head);
-                    if ready {
-                        println!(
-                            "TMP: RowIter at {min_pos} is Ready, so closing 1 out of {}",
-                            row_iters.len()
-                        );
-
-                        let _ = row_iters.swap_remove(min_pos);
-                    }
-                } else {
-                    panic!("Could not find element while row_iters is not empty.")
-                }
+            if right < len && precedes(self.heap[right], self.heap[first]) {
+                first = right;
             }
+            if first == pos {
+                break;
+            }
+            self.heap.swap(pos, first);
+            pos = first;
         }
+    }
 
-        // if report {
-        //     println!("TMP: Handled first iteration and written an item [press NewLine]");
-        //     let mut null = Default::default();
-        //     std::io::stdin().read_line(&mut null);
+    /// Remove the top of the heap.
+    pub(crate) fn pop<P>(&mut self, precedes: &P) -> Option<usize>
+    where
+        P: Fn(usize, usize) -> bool,
+    {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let top = self.heap.swap_remove(0);
+        if !self.heap.is_empty() {
+            self.sift_down(0, precedes);
+        }
+        Some(top)
+    }
+}
 
-        //     report = false;
-        // }
-        // barriers.add_barrier();
-        // b4 = 4;
+/// Returns the strict order of inputs 'i' and 'j' based on their heads, where 'smaller' is true when the first row sorts before
+/// or is equal to the second row. On equal rows the input with the lowest index goes first.
+fn input_precedes<F>(smaller: &F, head_i: &Row, head_j: &Row, i: usize, j: usize) -> bool
+where
+    F: Fn(&Row, &Row) -> bool,
+{
+    if i < j {
+        smaller(head_i, head_j)
+    } else {
+        !smaller(head_j, head_i)
     }
+}
+
+/// Iterator that merges the rows of a series of sorted RowIterExt's into a single sorted stream.
+/// 'smaller' should return true if the first row sorts before or is equal to the second row. In that case the row of the input
+/// with the lowest index is returned first on equal rows, so the merge is stable.
+/// The inputs are kept in a binary heap on their head, so selecting the next row takes O(log k) comparisons for k inputs.
+pub struct MergeIter<'a, F> {
+    row_iters: Vec<RowIterExt<'a>>,
+    heap: MergeHeap,
+    smaller: F,
+    verifiers: Option<Vec<InputVerifier>>,
+}
+
+impl<'a, F> MergeIter<'a, F>
+where
+    F: Fn(&Row, &Row) -> bool,
+{
+    pub fn new(row_iters: Vec<RowIterExt<'a>>, smaller: F) -> Self {
+        let precedes = |i: usize, j: usize| {
+            input_precedes(&smaller, row_iters[i].head().as_ref().unwrap(), row_iters[j].head().as_ref().unwrap(), i, j)
+        };
+        // empty inputs are not part of the heap
+        let non_empty = (0..row_iters.len())
+            .filter(|i| row_iters[*i].head().is_some())
+            .collect();
+        let heap = MergeHeap::new(non_empty, &precedes);
+        MergeIter {
+            row_iters,
+            heap,
+            smaller,
+            verifiers: None,
+        }
+    }
+
+    /// Verify the order of each input while merging, where 'verifiers' has one verifier per input.
+    pub fn with_verifiers(mut self, verifiers: Vec<InputVerifier>) -> Self {
+        assert_eq!(verifiers.len(), self.row_iters.len(), "Expected one verifier per input");
+        self.verifiers = Some(verifiers);
+        self
+    }
+}
+
+impl<'a, F> Iterator for MergeIter<'a, F>
+where
+    F: Fn(&Row, &Row) -> bool,
+{
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        let min_pos = self.heap.peek()?;
+        let (head, ready) = self.row_iters[min_pos].update_head();
+        if let (Some(verifiers), false) = (&mut self.verifiers, ready) {
+            let next = self.row_iters[min_pos].head().as_ref().unwrap();
+            verifiers[min_pos].check(&head, next, &self.smaller);
+        }
+
+        let row_iters = &self.row_iters;
+        let smaller = &self.smaller;
+        let precedes = |i: usize, j: usize| {
+            input_precedes(smaller, row_iters[i].head().as_ref().unwrap(), row_iters[j].head().as_ref().unwrap(), i, j)
+        };
+        if ready {
+            self.heap.pop(&precedes);
+        } else {
+            self.heap.sift_down(0, &precedes);
+        }
+        Some(head)
+    }
+}
+
+/// Open input 'path', such that its rows are mapped to the output 'schema' according to 'mapping'.
+fn open_input<'a>(path: &'a str, mapping: &'a InputMapping, schema: &Type, options: &MergeOptions) -> RowIterExt<'a> {
+    let message_type = Some(mapping.message_type.as_str());
+    let row_iter = match options.read_ahead {
+        Some(read_ahead) => RowIterExt::new_prefetched(path, message_type, read_ahead),
+        None => RowIterExt::new_projected(path, message_type, None),
+    }
+    .with_progress(options.progress.clone());
+    if mapping.needs_mapping() {
+        let mapping = mapping.clone();
+        row_iter.with_row_mapper(Box::new(move |row: Row| mapping.map_row(row)), schema.clone())
+    } else {
+        row_iter
+    }
+}
+
+/// Open all inputs of a merge. Panics if all inputs are empty.
+fn open_inputs<'a>(paths: &[&'a str], reconciliation: &'a Reconciliation, options: &MergeOptions) -> Vec<RowIterExt<'a>> {
+    let row_iters: Vec<RowIterExt> = paths
+        .iter()
+        .zip(reconciliation.inputs.iter())
+        .map(|(p, mapping)| open_input(p, mapping, &reconciliation.schema, options))
+        .collect();
+
+    if row_iters.iter().all(|rie| rie.head().is_none()) {
+        panic!("Nothing to merge");
+    }
+    row_iters
+}
+
+/// Merge the 'row_iters' of the inputs 'paths' and pass the merged rows to 'row_processor'. The 'options' select the
+/// compaction and the verification of the merge, where 'schema' is the schema of the merged rows.
+fn merge_rows<F, P>(
+    row_iters: Vec<RowIterExt>,
+    paths: &[&str],
+    schema: Arc<Type>,
+    options: &MergeOptions,
+    smaller: F,
+    mut row_processor: P,
+) where
+    F: Fn(&Row, &Row) -> bool,
+    P: FnMut(Row),
+{
+    let compactor = options
+        .compaction
+        .as_ref()
+        .map(|compaction| Compactor::new(compaction, schema));
+    let mut merged = MergeIter::new(row_iters, &smaller);
+    if options.verify_sorted {
+        merged = merged.with_verifiers(paths.iter().map(|path| InputVerifier::new(path)).collect());
+    }
+    match &compactor {
+        Some(compactor) => compactor.compact(merged, &smaller).for_each(&mut row_processor),
+        None => merged.for_each(&mut row_processor),
+    }
+}
+
+/// Merge the sorted inputs, where the rows of each input are mapped to the output schema according to the 'reconciliation'.
+/// The merged rows are projected on 'options.columns', where the 'sort_metadata' refers to the rows before the projection.
+fn merge_parquet_aux<F>(
+    paths: Vec<&str>,
+    merged_path: &str,
+    reconciliation: &Reconciliation,
+    sort_metadata: Option<SortMetadata>,
+    options: &MergeOptions,
+    smaller: F,
+) where
+    F: Fn(&Row, &Row) -> bool,
+{
+    let row_iters = open_inputs(&paths, reconciliation, options);
+
+    let projection = options.output_projection(&reconciliation.schema);
+    let (schema, sort_metadata) = match &projection {
+        Some(projection) => (
+            projection.schema(),
+            sort_metadata.and_then(|sm| projection.sort_metadata(&sm)),
+        ),
+        None => (Arc::clone(&reconciliation.schema), sort_metadata),
+    };
+    let mut row_writer = RowWriteBuffer::new_with_progress(
+        merged_path,
+        schema,
+        10000,
+        sort_metadata.map(|sm| sm.to_key_value_metadata()),
+        options.progress.clone(),
+    )
+    .unwrap();
+
+    options.progress.phase(merged_path, "merge");
+    merge_rows(
+        row_iters,
+        &paths,
+        Arc::clone(&reconciliation.schema),
+        options,
+        smaller,
+        |row: Row| match &projection {
+            Some(projection) => row_writer.append_row(projection.map_row(&row)),
+            None => row_writer.append_row(row),
+        },
+    );
 
-    println!("Closing the RowWriteBuffer  row_writer.close()");
     row_writer.close();
-    // println!("Closed\nUseless memory for checks contains {} (expect 10)", b1 + b2 + b3 + b4);
 }
 
 // // The more imperative (and less general) implementation. Complex due to the deeply nested if-then
@@ -174,3 +485,206 @@ pub fn merge_parquet(paths: Vec<&str>, merged_path: &str, smaller: fn(&Row, &Row
 //     }
 
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_parquet_by_key, MergeHeap, MergeIter, MergeOptions};
+    use crate::rowiterext::{read_rows, RowIterExt};
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::{sync::Arc, time::Instant};
+
+    /// Merge the sorted 'inputs' with the heap, returning (input, value) pairs in output order.
+    fn heap_merge(inputs: &[Vec<i64>]) -> Vec<(usize, i64)> {
+        let mut pos = vec![0; inputs.len()];
+        let head = |pos: &Vec<usize>, i: usize| inputs[i][pos[i]];
+        let mut heap = {
+            let precedes = |i: usize, j: usize| (head(&pos, i), i) < (head(&pos, j), j);
+            MergeHeap::new((0..inputs.len()).filter(|i| !inputs[*i].is_empty()).collect(), &precedes)
+        };
+        let mut merged = Vec::new();
+        while let Some(top) = heap.peek() {
+            merged.push((top, head(&pos, top)));
+            pos[top] += 1;
+            let precedes = |i: usize, j: usize| (head(&pos, i), i) < (head(&pos, j), j);
+            if pos[top] == inputs[top].len() {
+                heap.pop(&precedes);
+            } else {
+                heap.sift_down(0, &precedes);
+            }
+        }
+        merged
+    }
+
+    /// The linear selection that was used before the heap: a scan over all inputs for each output row.
+    fn linear_merge(inputs: &[Vec<i64>]) -> Vec<(usize, i64)> {
+        let mut pos = vec![0; inputs.len()];
+        let mut active: Vec<usize> = (0..inputs.len()).filter(|i| !inputs[*i].is_empty()).collect();
+        let mut merged = Vec::new();
+        while let Some((min_idx, _)) = active
+            .iter()
+            .enumerate()
+            .reduce(|acc, other| if inputs[*acc.1][pos[*acc.1]] <= inputs[*other.1][pos[*other.1]] { acc } else { other })
+        {
+            let input = active[min_idx];
+            merged.push((input, inputs[input][pos[input]]));
+            pos[input] += 1;
+            if pos[input] == inputs[input].len() {
+                active.remove(min_idx);
+            }
+        }
+        merged
+    }
+
+    /// Write each input to a file and merge the files with a MergeIter, returning (input, value) pairs in output order.
+    fn merge_iter(inputs: &[Vec<i64>]) -> Vec<(usize, i64)> {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let schema = Arc::new(parse_message_type("message schema { REQUIRED INT64 value; REQUIRED INT64 input; }").unwrap());
+        let paths: Vec<String> = inputs
+            .iter()
+            .enumerate()
+            .map(|(input, values)| {
+                let path = scratch.path(&format!("input-{input}.parquet"));
+                // small row-groups, such that the heads move across row-groups
+                let mut row_writer = RowWriteBuffer::new(&path, Arc::clone(&schema), 2).unwrap();
+                values.iter().for_each(|value| {
+                    row_writer.append_row(create_row(vec![
+                        ("value".to_owned(), Field::Long(*value)),
+                        ("input".to_owned(), Field::Long(input as i64)),
+                    ]))
+                });
+                row_writer.close();
+                path
+            })
+            .collect();
+
+        let row_iters = paths.iter().map(|path| RowIterExt::new(path)).collect();
+        let smaller = |left: &Row, right: &Row| left.get_long(0).unwrap() <= right.get_long(0).unwrap();
+        MergeIter::new(row_iters, smaller)
+            .map(|row| (row.get_long(1).unwrap() as usize, row.get_long(0).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_iter_is_stable() {
+        let inputs = vec![vec![1, 3, 3, 7], vec![], vec![0, 3, 8], vec![3, 3], vec![2]];
+        let merged = merge_iter(&inputs);
+
+        let values: Vec<i64> = merged.iter().map(|(_, value)| *value).collect();
+        assert_eq!(values, vec![0, 1, 2, 3, 3, 3, 3, 3, 7, 8]);
+        // equal values are returned in input-order
+        let inputs_of_3: Vec<usize> = merged.iter().filter(|(_, value)| *value == 3).map(|(input, _)| *input).collect();
+        assert_eq!(inputs_of_3, vec![0, 0, 2, 3, 3]);
+        assert_eq!(merged, linear_merge(&inputs));
+    }
+
+    #[test]
+    fn test_merge_iter_matches_linear_selection() {
+        let inputs: Vec<Vec<i64>> = (0..12).map(|input| (0..50).map(|i| i * 12 / 5 + input % 7).collect()).collect();
+        assert_eq!(merge_iter(&inputs), linear_merge(&inputs));
+    }
+
+    #[test]
+    fn test_merge_parquet_by_key() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let (first, second, merged) =
+            (scratch.path("first.parquet"), scratch.path("second.parquet"), scratch.path("merged.parquet"));
+        // the second input has its columns in another order, the key is resolved by name.
+        let inputs = [
+            (&first, "message schema { REQUIRED BINARY account (UTF8); REQUIRED INT64 ts; REQUIRED INT64 input; }"),
+            (&second, "message schema { REQUIRED INT64 ts; REQUIRED BINARY account (UTF8); REQUIRED INT64 input; }"),
+        ];
+        let keys = [vec![("a", 1), ("a", 2), ("b", 1), ("c", 5)], vec![("a", 2), ("b", 0), ("b", 1), ("c", 5)]];
+        inputs.iter().zip(&keys).enumerate().for_each(|(input, ((path, message_type), keys))| {
+            let schema = Arc::new(parse_message_type(message_type).unwrap());
+            let mut row_writer = RowWriteBuffer::new(path, Arc::clone(&schema), 2).unwrap();
+            keys.iter().for_each(|(account, ts)| {
+                let fields = schema.get_fields().iter().map(|column| {
+                    let field = match column.name() {
+                        "account" => Field::Str(account.to_string()),
+                        "ts" => Field::Long(*ts),
+                        _ => Field::Long(input as i64),
+                    };
+                    (column.name().to_owned(), field)
+                });
+                row_writer.append_row(create_row(fields.collect()))
+            });
+            row_writer.close();
+        });
+
+        merge_parquet_by_key(vec![first.as_str(), second.as_str()], &merged, &["account", "ts"], &MergeOptions::default());
+
+        let field = |row: &Row, name: &str| {
+            let (_, field) = row.get_column_iter().find(|(column, _)| *column == name).unwrap();
+            field.clone()
+        };
+        let merged: Vec<(Field, Field, Field)> = read_rows(&merged, None, inputs[0].1)
+            .iter()
+            .map(|row| (field(row, "account"), field(row, "ts"), field(row, "input")))
+            .collect();
+        let expected: Vec<(Field, Field, Field)> = [
+            ("a", 1, 0),
+            ("a", 2, 0),
+            ("a", 2, 1),
+            ("b", 0, 1),
+            ("b", 1, 0),
+            ("b", 1, 1),
+            ("c", 5, 0),
+            ("c", 5, 1),
+        ]
+        .iter()
+        .map(|(account, ts, input)| (Field::Str(account.to_string()), Field::Long(*ts), Field::Long(*input)))
+        .collect();
+        // on an equal composite key the row of the first input comes first.
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_merge_heap_is_stable() {
+        let inputs = vec![vec![1, 3, 3, 7], vec![], vec![0, 3, 8], vec![3, 3], vec![2]];
+        let merged = heap_merge(&inputs);
+
+        let values: Vec<i64> = merged.iter().map(|(_, value)| *value).collect();
+        assert_eq!(values, vec![0, 1, 2, 3, 3, 3, 3, 3, 7, 8]);
+        // equal values are returned in input-order
+        let inputs_of_3: Vec<usize> = merged.iter().filter(|(_, value)| *value == 3).map(|(input, _)| *input).collect();
+        assert_eq!(inputs_of_3, vec![0, 0, 2, 3, 3]);
+        assert_eq!(merged, linear_merge(&inputs));
+    }
+
+    #[test]
+    fn test_merge_heap_matches_linear_selection() {
+        for num_inputs in [2, 10, 100] {
+            let inputs: Vec<Vec<i64>> = (0..num_inputs)
+                .map(|input| (0..10_000 / num_inputs).map(|i| i * num_inputs + input % 7).collect())
+                .collect();
+            assert_eq!(heap_merge(&inputs), linear_merge(&inputs));
+        }
+    }
+
+    /// Compare the heap with the linear selection. Run with 'cargo test --release -- --ignored bench_merge_selection --nocapture'
+    #[test]
+    #[ignore]
+    fn bench_merge_selection() {
+        for num_inputs in [2, 10, 100, 500] {
+            let inputs: Vec<Vec<i64>> = (0..num_inputs)
+                .map(|input| (0..200_000 / num_inputs).map(|i| i * num_inputs + input).collect())
+                .collect();
+
+            let now = Instant::now();
+            let heap_merged = heap_merge(&inputs);
+            let heap_elapsed = now.elapsed();
+
+            let now = Instant::now();
+            let linear_merged = linear_merge(&inputs);
+            let linear_elapsed = now.elapsed();
+
+            assert_eq!(heap_merged, linear_merged);
+            println!("{num_inputs} inputs: heap {heap_elapsed:?}  linear {linear_elapsed:?}");
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::reconcile::reconcile_files;
+use super::verify::check_row_group_order;
+use super::{merge_rows, open_input, open_inputs, MergeOptions};
+use crate::{
+    find_column,
+    row_compare::{compare_fields, get_field},
+    sort::{rows_per_file, SortedOutput},
+    sort_metadata::SortMetadata,
+};
+use parquet::record::Row;
+use std::{cmp::Ordering, sync::Arc};
+
+/// Compact the (small) files 'inputs' into files '<output_prefix>-<NNNNN>.parquet' of about 'target_file_bytes' each.
+/// With a 'key' the inputs should be sorted on that key, and are merged such that the outputs are sorted on it as well.
+/// The key-ranges of the outputs do not overlap, and a manifest '<output_prefix>-manifest.parquet' lists the min/max key
+/// of each output. Without a key the rows are copied in input order, and only a single input is open at a time.
+/// The outputs are rolled over on a number of rows, estimated from the average row-size of the inputs (see
+/// 'rows_per_file'), so their size only approximates 'target_file_bytes'. With a key an output is extended up to the next
+/// change of the key.
+pub fn compact(inputs: Vec<&str>, output_prefix: &str, target_file_bytes: u64, key: Option<&str>) {
+    compact_with_options(inputs, output_prefix, target_file_bytes, key, &MergeOptions::default())
+}
+
+/// Compact the files 'inputs' (see 'compact'), using the 'options' to select the behaviour of the merge, such as the
+/// reconciliation of the schemas of the inputs. 'options.columns' selects the columns of the outputs, where the outputs are
+/// only marked as sorted (and get a manifest) if the key is one of these columns.
+pub fn compact_with_options(
+    inputs: Vec<&str>,
+    output_prefix: &str,
+    target_file_bytes: u64,
+    key: Option<&str>,
+    options: &MergeOptions,
+) {
+    assert!(!inputs.is_empty(), "Nothing to compact");
+
+    let key_columns: Vec<&str> = key.into_iter().collect();
+    let reconciliation = reconcile_files(&inputs, options.schema, options.read_selection(&key_columns).as_deref());
+    let schema = Arc::clone(&reconciliation.schema);
+    let rows_per_file = Some(rows_per_file(&inputs, target_file_bytes));
+    let projection = options.output_projection(&schema);
+    let output_schema = projection.as_ref().map_or_else(|| Arc::clone(&schema), |projection| projection.schema());
+
+    match key {
+        Some(key) => {
+            if options.verify_sorted {
+                inputs.iter().for_each(|path| check_row_group_order(path, key, false));
+            }
+            let (col, _) = find_column(Arc::clone(&schema), key);
+            let sort_metadata = SortMetadata {
+                key: key.to_owned(),
+                column: col,
+                descending: false,
+                algorithm: "compact".to_owned(),
+            };
+            let sort_metadata = match &projection {
+                Some(projection) => projection.sort_metadata(&sort_metadata),
+                None => Some(sort_metadata),
+            };
+            let mut output = SortedOutput::new(
+                output_prefix,
+                output_schema,
+                sort_metadata,
+                rows_per_file,
+                options.progress.clone(),
+            )
+            .with_projection(projection);
+
+            let row_iters = open_inputs(&inputs, &reconciliation, options);
+            let smaller =
+                move |row_1: &Row, row_2: &Row| compare_fields(get_field(row_1, col), get_field(row_2, col)) != Ordering::Greater;
+            options.progress.phase(output_prefix, "compact");
+            merge_rows(row_iters, &inputs, schema, options, smaller, |row: Row| output.append_row(row));
+            output.close();
+        }
+        None => {
+            assert!(options.compaction.is_none(), "Upsert-compaction requires a key");
+            let mut output = SortedOutput::new(output_prefix, output_schema, None, rows_per_file, options.progress.clone())
+                .with_projection(projection);
+
+            options.progress.phase(output_prefix, "compact");
+            inputs
+                .iter()
+                .zip(reconciliation.inputs.iter())
+                .for_each(|(path, mapping)| {
+                    let mut input = open_input(path, mapping, &schema, options);
+                    while input.head().is_some() {
+                        output.append_row(input.update_head().0);
+                    }
+                });
+            output.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compact;
+    use crate::object_size::get_object_size;
+    use crate::rowiterext::read_rows;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use crate::sort::rows_per_file;
+    use parquet::{
+        record::{Field, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::{path::Path, sync::Arc};
+
+    const MESSAGE_TYPE: &str = "message schema { REQUIRED INT64 id; REQUIRED INT64 input; }";
+
+    /// Write 3 small inputs with interleaved ids, each sorted on the id.
+    fn write_inputs(scratch: &mut ScratchSpace) -> Vec<String> {
+        let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+        (0..3)
+            .map(|input| {
+                let path = scratch.path(&format!("input-{input}.parquet"));
+                let mut row_writer = RowWriteBuffer::new(&path, Arc::clone(&schema), 5).unwrap();
+                (0..12).for_each(|i| {
+                    row_writer.append_row(create_row(vec![
+                        ("id".to_owned(), Field::Long(i * 3 + input)),
+                        ("input".to_owned(), Field::Long(input)),
+                    ]))
+                });
+                row_writer.close();
+                path
+            })
+            .collect()
+    }
+
+    /// Compact the inputs to files of 10 rows, and return the (id, input) of the rows of each output.
+    fn compact_inputs(key: Option<&str>) -> (Vec<Vec<(i64, i64)>>, Option<Vec<(i64, i64)>>) {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let inputs = write_inputs(&mut scratch);
+        let inputs: Vec<&str> = inputs.iter().map(|path| path.as_str()).collect();
+        let bytes_per_row = inputs.iter().map(|path| get_object_size(path)).sum::<u64>() / 36;
+        let target_file_bytes = 10 * bytes_per_row;
+        assert_eq!(rows_per_file(&inputs, target_file_bytes), 10);
+
+        let output_prefix = scratch.path("compacted");
+        compact(inputs, &output_prefix, target_file_bytes, key);
+
+        let outputs = (0..)
+            .map(|idx| format!("{output_prefix}-{idx:05}.parquet"))
+            .take_while(|path| Path::new(path).exists())
+            .map(|path| {
+                read_rows(&path, None, MESSAGE_TYPE)
+                    .iter()
+                    .map(|row| (row.get_long(0).unwrap(), row.get_long(1).unwrap()))
+                    .collect()
+            })
+            .collect();
+        let manifest_path = format!("{output_prefix}-manifest.parquet");
+        let manifest = Path::new(&manifest_path).exists().then(|| {
+            let manifest_type =
+                "message manifest { REQUIRED BINARY path (UTF8); REQUIRED INT64 min_key; REQUIRED INT64 max_key; }";
+            read_rows(&manifest_path, None, manifest_type)
+                .iter()
+                .map(|row| (row.get_long(1).unwrap(), row.get_long(2).unwrap()))
+                .collect()
+        });
+        (outputs, manifest)
+    }
+
+    #[test]
+    fn test_compact_on_key() {
+        let (outputs, manifest) = compact_inputs(Some("id"));
+        let sizes: Vec<usize> = outputs.iter().map(|output| output.len()).collect();
+        assert_eq!(sizes, vec![10, 10, 10, 6]);
+        let ids: Vec<i64> = outputs.concat().iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, (0..36).collect::<Vec<i64>>());
+
+        let manifest = manifest.expect("A compaction on a key writes a manifest");
+        assert_eq!(manifest, vec![(0, 9), (10, 19), (20, 29), (30, 35)]);
+        assert!(manifest.windows(2).all(|w| w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn test_compact_without_key() {
+        let (outputs, manifest) = compact_inputs(None);
+        assert_eq!(outputs.len(), 4);
+        // the rows are copied in input order
+        let rows = outputs.concat();
+        let expected: Vec<(i64, i64)> = (0..3).flat_map(|input| (0..12).map(move |i| (i * 3 + input, input))).collect();
+        assert_eq!(rows, expected);
+        assert!(manifest.is_none());
+    }
+}
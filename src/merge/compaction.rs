@@ -0,0 +1,193 @@
+use crate::{
+    find_column,
+    row_compare::{compare_fields, get_field},
+};
+use itertools::Itertools;
+use parquet::{
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::{cmp::Ordering, sync::Arc};
+
+/// Which version of a key survives a compaction.
+#[derive(Clone, Debug)]
+pub enum KeepVersion {
+    /// Keep the row of the newest input, where the inputs are passed from oldest (the base) to newest.
+    NewestInput,
+    /// Keep the row with the largest value in the named column, for example a 'version' or 'datetime' column.
+    /// On ties the row of the newest input is kept.
+    MaxBy(String),
+}
+
+/// Upsert-compaction of a merge: of the rows with an equal key only a single version is kept.
+#[derive(Clone, Debug)]
+pub struct CompactionOptions {
+    pub keep: KeepVersion,
+    /// Name of a boolean column that marks deletes. If the surviving version of a key is a tombstone the key is removed
+    /// from the output. A null is not a tombstone.
+    pub tombstone: Option<String>,
+}
+
+impl CompactionOptions {
+    /// The columns that are read by the compaction.
+    pub fn columns(&self) -> Vec<&str> {
+        let version = match &self.keep {
+            KeepVersion::NewestInput => None,
+            KeepVersion::MaxBy(name) => Some(name.as_str()),
+        };
+        version.into_iter().chain(self.tombstone.as_deref()).collect()
+    }
+}
+
+/// Compacts a merged stream of rows, based on the columns resolved from the 'CompactionOptions'.
+pub struct Compactor {
+    version_col: Option<usize>,
+    tombstone_col: Option<usize>,
+}
+
+impl Compactor {
+    pub fn new(options: &CompactionOptions, schema: Arc<Type>) -> Self {
+        let version_col = match &options.keep {
+            KeepVersion::NewestInput => None,
+            KeepVersion::MaxBy(name) => Some(find_column(Arc::clone(&schema), name).0),
+        };
+        let tombstone_col = options
+            .tombstone
+            .as_ref()
+            .map(|name| find_column(schema, name).0);
+        Self {
+            version_col,
+            tombstone_col,
+        }
+    }
+
+    /// The merge is stable, so 'next' comes from the same or a newer input than 'prev'.
+    fn select(&self, prev: Row, next: Row) -> Row {
+        match self.version_col {
+            None => next,
+            Some(col) => {
+                if compare_fields(get_field(&next, col), get_field(&prev, col)) == Ordering::Less {
+                    prev
+                } else {
+                    next
+                }
+            }
+        }
+    }
+
+    fn is_tombstone(&self, row: &Row) -> bool {
+        match self.tombstone_col.map(|col| get_field(row, col)) {
+            None | Some(Field::Null) | Some(Field::Bool(false)) => false,
+            Some(Field::Bool(true)) => true,
+            Some(other) => panic!("The tombstone column should be a boolean, but it contains {other:?}"),
+        }
+    }
+
+    /// Streaming compaction of the output of a (stable) merge. Rows are considered equal when 'smaller' holds in both directions.
+    pub fn compact<'a, I, F>(&'a self, rows: I, smaller: &'a F) -> impl Iterator<Item = Row> + 'a
+    where
+        I: Iterator<Item = Row> + 'a,
+        F: Fn(&Row, &Row) -> bool,
+    {
+        rows.coalesce(move |prev, next| {
+            if smaller(&prev, &next) && smaller(&next, &prev) {
+                Ok(self.select(prev, next))
+            } else {
+                Err((prev, next))
+            }
+        })
+        .filter(move |row| !self.is_tombstone(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompactionOptions, Compactor, KeepVersion};
+    use crate::merge::{merge_parquet_with_options, MergeOptions};
+    use crate::rowiterext::read_rows;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    /// Rows in the order of a stable merge of a base (version 1) and two deltas.
+    fn rows() -> Vec<Row> {
+        vec![(1, 1, false), (1, 3, false), (1, 2, false), (2, 1, false), (2, 2, true), (3, 1, false)]
+            .into_iter()
+            .map(|(id, version, deleted)| {
+                create_row(vec![
+                    ("id".to_owned(), Field::Long(id)),
+                    ("version".to_owned(), Field::Int(version)),
+                    ("deleted".to_owned(), Field::Bool(deleted)),
+                ])
+            })
+            .collect()
+    }
+
+    fn compact_versions(keep: KeepVersion) -> Vec<(i64, i32)> {
+        let schema = Arc::new(
+            parse_message_type("message schema { REQUIRED INT64 id; REQUIRED INT32 version; REQUIRED BOOLEAN deleted; }")
+                .unwrap(),
+        );
+        let options = CompactionOptions {
+            keep,
+            tombstone: Some("deleted".to_owned()),
+        };
+        let compactor = Compactor::new(&options, schema);
+        let smaller = |left: &Row, right: &Row| left.get_long(0).unwrap() <= right.get_long(0).unwrap();
+        compactor
+            .compact(rows().into_iter(), &smaller)
+            .map(|row| (row.get_long(0).unwrap(), row.get_int(1).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compact() {
+        // key 2 is removed by the tombstone of its newest version
+        assert_eq!(compact_versions(KeepVersion::NewestInput), vec![(1, 2), (3, 1)]);
+        assert_eq!(compact_versions(KeepVersion::MaxBy("version".to_owned())), vec![(1, 3), (3, 1)]);
+    }
+
+    #[test]
+    fn test_merge_with_tombstones() {
+        const MESSAGE_TYPE: &str =
+            "message schema { REQUIRED INT64 id; REQUIRED BOOLEAN deleted; OPTIONAL DOUBLE amount; }";
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let (base, delta, merged) =
+            (scratch.path("base.parquet"), scratch.path("delta.parquet"), scratch.path("merged.parquet"));
+        let write = |path: &str, rows: &[(i64, bool, Option<f64>)]| {
+            let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+            let mut row_writer = RowWriteBuffer::new(path, schema, 10).unwrap();
+            rows.iter().for_each(|(id, deleted, amount)| {
+                row_writer.append_row(create_row(vec![
+                    ("id".to_owned(), Field::Long(*id)),
+                    ("deleted".to_owned(), Field::Bool(*deleted)),
+                    ("amount".to_owned(), amount.map_or(Field::Null, Field::Double)),
+                ]))
+            });
+            row_writer.close();
+        };
+        write(&base, &[(1, false, Some(1.5)), (2, false, Some(2.5)), (3, false, None)]);
+        // the delta updates key 1, deletes key 2 and inserts key 4.
+        write(&delta, &[(1, false, Some(10.5)), (2, true, None), (4, false, Some(4.5))]);
+
+        let options = MergeOptions {
+            compaction: Some(CompactionOptions {
+                keep: KeepVersion::NewestInput,
+                tombstone: Some("deleted".to_owned()),
+            }),
+            ..MergeOptions::default()
+        };
+        let smaller = |left: &Row, right: &Row| left.get_long(0).unwrap() <= right.get_long(0).unwrap();
+        merge_parquet_with_options(vec![base.as_str(), delta.as_str()], &merged, smaller, &options);
+
+        let rows: Vec<(i64, bool, Option<f64>)> = read_rows(&merged, None, MESSAGE_TYPE)
+            .iter()
+            .map(|row| (row.get_long(0).unwrap(), row.get_bool(1).unwrap(), row.get_double(2).ok()))
+            .collect();
+        assert_eq!(rows, vec![(1, false, Some(10.5)), (3, false, None), (4, false, Some(4.5))]);
+    }
+}
@@ -0,0 +1,251 @@
+use super::join::{check_sort_order, take_key_group};
+use super::reconcile::{reconcile_files, SchemaStrategy};
+use crate::{
+    find_column,
+    row_compare::{compare_fields, get_field},
+    rowiterext::RowIterExt,
+    rowwritebuffer::{create_row, RowWriteBuffer},
+};
+use parquet::{
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::{cmp::Ordering, sync::Arc};
+
+/// The kind of difference of a key between the old and the new file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    /// The key only occurs in the new file.
+    Inserted,
+    /// The key only occurs in the old file.
+    Deleted,
+    /// The key occurs in both files, but at least one of the columns differs.
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Inserted => "inserted",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Modified => "modified",
+        }
+    }
+}
+
+/// A row that differs between the old and the new file.
+#[derive(Debug)]
+pub struct RowChange {
+    pub kind: ChangeKind,
+    /// The row in the old file, which is None for an inserted row.
+    pub old: Option<Row>,
+    /// The row in the new file, which is None for a deleted row.
+    pub new: Option<Row>,
+    /// The names of the columns that differ, in schema order. Only filled for a modified row.
+    pub changed_columns: Vec<String>,
+}
+
+/// The number of rows per kind of change.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DiffStats {
+    pub inserted: u64,
+    pub deleted: u64,
+    pub modified: u64,
+    pub unchanged: u64,
+}
+
+impl DiffStats {
+    fn register(&mut self, change: Option<&RowChange>) {
+        match change.map(|change| change.kind) {
+            None => self.unchanged += 1,
+            Some(ChangeKind::Inserted) => self.inserted += 1,
+            Some(ChangeKind::Deleted) => self.deleted += 1,
+            Some(ChangeKind::Modified) => self.modified += 1,
+        }
+    }
+}
+
+/// Equality of two values, where floating point values are compared bitwise, such that a NaN equals itself.
+fn same_value(old: &Field, new: &Field) -> bool {
+    match (old, new) {
+        (Field::Float(old), Field::Float(new)) => old.to_bits() == new.to_bits(),
+        (Field::Double(old), Field::Double(new)) => old.to_bits() == new.to_bits(),
+        (Field::Group(old), Field::Group(new)) => {
+            old.len() == new.len()
+                && old
+                    .get_column_iter()
+                    .zip(new.get_column_iter())
+                    .all(|((old_name, old), (new_name, new))| old_name == new_name && same_value(old, new))
+        }
+        (Field::ListInternal(old), Field::ListInternal(new)) => {
+            old.elements().len() == new.elements().len()
+                && old.elements().iter().zip(new.elements()).all(|(old, new)| same_value(old, new))
+        }
+        (Field::MapInternal(old), Field::MapInternal(new)) => {
+            old.entries().len() == new.entries().len()
+                && old
+                    .entries()
+                    .iter()
+                    .zip(new.entries())
+                    .all(|((old_key, old), (new_key, new))| same_value(old_key, new_key) && same_value(old, new))
+        }
+        (old, new) => old == new,
+    }
+}
+
+/// Compare the old and the new version of a row with the same key. Returns None if the row did not change.
+fn compare_versions(old: Option<Row>, new: Option<Row>) -> Option<RowChange> {
+    let (kind, changed_columns) = match (&old, &new) {
+        (Some(_), None) => (ChangeKind::Deleted, Vec::new()),
+        (None, Some(_)) => (ChangeKind::Inserted, Vec::new()),
+        (Some(old_row), Some(new_row)) => {
+            let changed_columns: Vec<String> = old_row
+                .get_column_iter()
+                .zip(new_row.get_column_iter())
+                .filter(|((_, old_field), (_, new_field))| !same_value(old_field, new_field))
+                .map(|((name, _), _)| name.clone())
+                .collect();
+            if changed_columns.is_empty() {
+                return None;
+            }
+            (ChangeKind::Modified, changed_columns)
+        }
+        (None, None) => panic!("A change requires an old or a new row"),
+    };
+    Some(RowChange {
+        kind,
+        old,
+        new,
+        changed_columns,
+    })
+}
+
+/// Stream the files 'old_path' and 'new_path', which are both sorted ascending on 'key', and pass each inserted, deleted
+/// and modified row to 'on_change'. Both files should have the same columns, although the order of the columns may differ;
+/// the rows are passed in the column-order of the old file. Rows with a duplicate key are paired in their order of appearance.
+pub fn diff_sorted_with<F>(old_path: &str, new_path: &str, key: &str, mut on_change: F) -> DiffStats
+where
+    F: FnMut(RowChange),
+{
+    check_sort_order(old_path, key);
+    check_sort_order(new_path, key);
+
+    let paths = [old_path, new_path];
+    let reconciliation = reconcile_files(&paths, SchemaStrategy::Strict, None);
+    let mut old = RowIterExt::new_projected(old_path, Some(reconciliation.inputs[0].message_type.as_str()), None);
+    let mut new = RowIterExt::new_projected(new_path, Some(reconciliation.inputs[1].message_type.as_str()), None);
+    let (col, _) = find_column(Arc::clone(&reconciliation.schema), key);
+
+    let mut stats = DiffStats::default();
+    let mut process = |old_row: Option<Row>, new_row: Option<Row>| {
+        let change = compare_versions(old_row, new_row);
+        stats.register(change.as_ref());
+        if let Some(change) = change {
+            on_change(change);
+        }
+    };
+
+    loop {
+        let order = match (old.head(), new.head()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(old_row), Some(new_row)) => compare_fields(get_field(old_row, col), get_field(new_row, col)),
+        };
+        match order {
+            Ordering::Less => process(Some(old.update_head().0), None),
+            Ordering::Greater => process(None, Some(new.update_head().0)),
+            Ordering::Equal => {
+                let key_value = get_field(old.head().as_ref().unwrap(), col).clone();
+                let mut old_group = take_key_group(&mut old, col, &key_value).into_iter();
+                let mut new_group = take_key_group(&mut new, col, &key_value).into_iter();
+                loop {
+                    match (old_group.next(), new_group.next()) {
+                        (None, None) => break,
+                        (old_row, new_row) => process(old_row, new_row),
+                    }
+                }
+            }
+        }
+    }
+    stats
+}
+
+fn utf8_column(name: &str) -> Arc<Type> {
+    Arc::new(
+        Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+            .with_repetition(Repetition::REQUIRED)
+            .with_converted_type(ConvertedType::UTF8)
+            .build()
+            .unwrap(),
+    )
+}
+
+/// Write the differences between 'old_path' and 'new_path' (see 'diff_sorted_with') to 'output_path'.
+/// The output starts with a column 'change' (inserted, deleted or modified) and a column 'changed_columns' (the
+/// comma-separated names of the modified columns), followed by the columns of the new row, or of the old row for a delete.
+pub fn diff_sorted(old_path: &str, new_path: &str, key: &str, output_path: &str) -> DiffStats {
+    let input_schema = RowIterExt::new(old_path).schema().clone();
+    let mut columns = vec![utf8_column("change"), utf8_column("changed_columns")];
+    input_schema.get_fields().iter().for_each(|column| {
+        assert!(
+            column.name() != "change" && column.name() != "changed_columns",
+            "The column '{}' of '{old_path}' conflicts with the columns of the diff",
+            column.name()
+        );
+        columns.push(Arc::clone(column));
+    });
+    let schema = Type::group_type_builder(input_schema.name())
+        .with_fields(&mut columns)
+        .build()
+        .unwrap();
+
+    let mut row_writer = RowWriteBuffer::new(output_path, Arc::new(schema), 10000).unwrap();
+    let stats = diff_sorted_with(old_path, new_path, key, |change| {
+        let mut fields = vec![
+            ("change".to_owned(), Field::Str(change.kind.as_str().to_owned())),
+            ("changed_columns".to_owned(), Field::Str(change.changed_columns.join(","))),
+        ];
+        let row = change.new.or(change.old).unwrap();
+        fields.extend(row.get_column_iter().map(|(name, field)| (name.clone(), field.clone())));
+        row_writer.append_row(create_row(fields));
+    });
+    row_writer.close();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_versions, ChangeKind};
+    use crate::rowwritebuffer::create_row;
+    use parquet::record::{Field, Row};
+
+    fn row(id: i64, account: &str, amount: i32) -> Row {
+        create_row(vec![
+            ("id".to_owned(), Field::Long(id)),
+            ("account".to_owned(), Field::Str(account.to_owned())),
+            ("amount".to_owned(), Field::Int(amount)),
+        ])
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert!(compare_versions(Some(row(1, "abc", 10)), Some(row(1, "abc", 10))).is_none());
+
+        let modified = compare_versions(Some(row(1, "abc", 10)), Some(row(1, "abd", 11))).unwrap();
+        assert_eq!(modified.kind, ChangeKind::Modified);
+        assert_eq!(modified.changed_columns, vec!["account", "amount"]);
+
+        let inserted = compare_versions(None, Some(row(2, "xyz", 5))).unwrap();
+        assert_eq!(inserted.kind, ChangeKind::Inserted);
+        assert!(inserted.changed_columns.is_empty());
+        assert_eq!(compare_versions(Some(row(3, "q", 1)), None).unwrap().kind, ChangeKind::Deleted);
+
+        let with_rate = |rate: f64| {
+            create_row(vec![("id".to_owned(), Field::Long(4)), ("rate".to_owned(), Field::Double(rate))])
+        };
+        assert!(compare_versions(Some(with_rate(f64::NAN)), Some(with_rate(f64::NAN))).is_none());
+        assert!(compare_versions(Some(with_rate(f64::NAN)), Some(with_rate(1.5))).is_some());
+    }
+}
@@ -0,0 +1,284 @@
+use super::reconcile::make_optional;
+use crate::{
+    find_column,
+    projection::copy_column,
+    row_compare::{compare_fields, get_field},
+    rowiterext::RowIterExt,
+    rowwritebuffer::{create_row, RowWriteBuffer},
+    sort_metadata::get_sort_metadata,
+};
+use parquet::{
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::{cmp::Ordering, sync::Arc};
+
+/// The rows that are written by a 'merge_join'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinType {
+    /// Only the combinations of rows with a key that occurs in both inputs.
+    Inner,
+    /// All rows of the left input. The right columns are null for a key that does not occur in the right input.
+    Left,
+    /// All rows of the right input. The left columns are null for a key that does not occur in the left input.
+    Right,
+    /// All rows of both inputs, where the columns of the other input are null for keys that only occur in one input.
+    Full,
+}
+
+impl JoinType {
+    fn keeps_left(self) -> bool {
+        matches!(self, JoinType::Left | JoinType::Full)
+    }
+
+    fn keeps_right(self) -> bool {
+        matches!(self, JoinType::Right | JoinType::Full)
+    }
+}
+
+/// The output schema of a join: the columns of the left input followed by the columns of the right input. A right column
+/// with the same name as a left column gets the suffix '_right'. The columns of an input that can be missing in the
+/// output (for example the right columns of a left join) become optional.
+fn join_schema(left: &Type, right: &Type, join_type: JoinType) -> Type {
+    let side_columns = |schema: &Type, optional: bool| -> Vec<Type> {
+        schema
+            .get_fields()
+            .iter()
+            .map(|column| if optional { make_optional(column) } else { column.as_ref().clone() })
+            .collect()
+    };
+    let left_columns = side_columns(left, join_type.keeps_right());
+    let right_columns = side_columns(right, join_type.keeps_left());
+
+    let mut columns: Vec<Arc<Type>> = left_columns.into_iter().map(Arc::new).collect();
+    right_columns.into_iter().for_each(|column| {
+        let basic_info = column.get_basic_info();
+        let name = basic_info.name();
+        let column = if left.get_fields().iter().any(|left_column| left_column.name() == name) {
+            copy_column(&column, &format!("{name}_right"), basic_info.repetition())
+        } else {
+            column.clone()
+        };
+        columns.push(Arc::new(column));
+    });
+
+    Type::group_type_builder(left.name())
+        .with_fields(&mut columns)
+        .build()
+        .unwrap()
+}
+
+/// Writes the combined rows of a join.
+struct JoinOutput {
+    names: Vec<String>,
+    num_left: usize,
+    row_writer: RowWriteBuffer,
+}
+
+impl JoinOutput {
+    fn new(path: &str, schema: Arc<Type>, num_left: usize) -> Self {
+        let names = schema
+            .get_fields()
+            .iter()
+            .map(|column| column.name().to_owned())
+            .collect();
+        JoinOutput {
+            names,
+            num_left,
+            row_writer: RowWriteBuffer::new(path, schema, 10000).unwrap(),
+        }
+    }
+
+    /// Write the combination of 'left' and 'right', where a missing row results in nulls for its columns.
+    fn write(&mut self, left: Option<&Row>, right: Option<&Row>) {
+        let fields = side_fields(left, self.num_left)
+            .into_iter()
+            .chain(side_fields(right, self.names.len() - self.num_left));
+        let row = create_row(self.names.iter().cloned().zip(fields).collect());
+        self.row_writer.append_row(row);
+    }
+
+    fn close(&mut self) {
+        self.row_writer.close();
+    }
+}
+
+fn side_fields(row: Option<&Row>, num_columns: usize) -> Vec<Field> {
+    match row {
+        Some(row) => row.get_column_iter().map(|(_, field)| field.clone()).collect(),
+        None => vec![Field::Null; num_columns],
+    }
+}
+
+/// Take the rows at the head of 'input' that have value 'key' in column 'col'.
+pub fn take_key_group(input: &mut RowIterExt, col: usize, key: &Field) -> Vec<Row> {
+    let mut group = Vec::new();
+    while input
+        .head()
+        .as_ref()
+        .map_or(false, |row| compare_fields(get_field(row, col), key) == Ordering::Equal)
+    {
+        group.push(input.update_head().0);
+    }
+    group
+}
+
+/// Panic if the sort-metadata of 'path' shows that it is not sorted ascending on 'key'.
+pub fn check_sort_order(path: &str, key: &str) {
+    if let Some(sort_metadata) = get_sort_metadata(path) {
+        assert!(
+            sort_metadata.key == key && !sort_metadata.descending,
+            "Input '{path}' should be sorted ascending on '{key}', but it is sorted on {sort_metadata:?}"
+        );
+    }
+}
+
+/// Join the files 'left_path' and 'right_path' on 'left_key' = 'right_key' and write the result to 'output_path'.
+/// Both inputs should be sorted ascending on their key (as is the output of 'sort'), such that both are streamed in a
+/// single pass. Only the rows of a single key are kept in memory, as a key that occurs multiple times in both inputs
+/// results in all combinations of these rows. A null key never matches.
+/// The output contains the columns of the left input followed by those of the right input (see 'join_schema').
+pub fn merge_join(
+    left_path: &str,
+    right_path: &str,
+    left_key: &str,
+    right_key: &str,
+    join_type: JoinType,
+    output_path: &str,
+) {
+    check_sort_order(left_path, left_key);
+    check_sort_order(right_path, right_key);
+
+    let mut left = RowIterExt::new(left_path);
+    let mut right = RowIterExt::new(right_path);
+    let left_schema = Arc::new(left.schema().clone());
+    let right_schema = Arc::new(right.schema().clone());
+    let (left_col, left_key_type) = find_column(Arc::clone(&left_schema), left_key);
+    let (right_col, right_key_type) = find_column(Arc::clone(&right_schema), right_key);
+    assert_eq!(
+        left_key_type.get_physical_type(),
+        right_key_type.get_physical_type(),
+        "The join-keys '{left_key}' and '{right_key}' should have the same type"
+    );
+
+    let schema = Arc::new(join_schema(&left_schema, &right_schema, join_type));
+    let mut output = JoinOutput::new(output_path, schema, left_schema.get_fields().len());
+
+    loop {
+        // the input with the smallest head goes first. Nulls sort first and never match.
+        let order = match (left.head(), right.head()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(left_row), Some(right_row)) => {
+                match (get_field(left_row, left_col), get_field(right_row, right_col)) {
+                    (Field::Null, _) => Ordering::Less,
+                    (_, Field::Null) => Ordering::Greater,
+                    (left_field, right_field) => compare_fields(left_field, right_field),
+                }
+            }
+        };
+        match order {
+            Ordering::Less => {
+                let (row, _) = left.update_head();
+                if join_type.keeps_left() {
+                    output.write(Some(&row), None);
+                }
+            }
+            Ordering::Greater => {
+                let (row, _) = right.update_head();
+                if join_type.keeps_right() {
+                    output.write(None, Some(&row));
+                }
+            }
+            Ordering::Equal => {
+                let key = get_field(left.head().as_ref().unwrap(), left_col).clone();
+                let left_group = take_key_group(&mut left, left_col, &key);
+                let right_group = take_key_group(&mut right, right_col, &key);
+                left_group.iter().for_each(|left_row| {
+                    right_group
+                        .iter()
+                        .for_each(|right_row| output.write(Some(left_row), Some(right_row)))
+                });
+            }
+        }
+    }
+
+    output.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_join, JoinType};
+    use crate::rowiterext::read_rows;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use parquet::{
+        record::{Field, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    fn write_input(path: &str, message_type: &str, rows: &[(i64, i32)]) {
+        let schema = Arc::new(parse_message_type(message_type).unwrap());
+        let names: Vec<String> = schema.get_fields().iter().map(|f| f.name().to_owned()).collect();
+        let mut row_writer = RowWriteBuffer::new(path, schema, 100).unwrap();
+        rows.iter().for_each(|(key, value)| {
+            row_writer.append_row(create_row(vec![
+                (names[0].clone(), Field::Long(*key)),
+                (names[1].clone(), Field::Int(*value)),
+            ]))
+        });
+        row_writer.close();
+    }
+
+    /// Join two small inputs and return (account, amount, account_id, limit) where a null is returned as -1.
+    fn join(join_type: JoinType) -> Vec<(i64, i32, i64, i32)> {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let (left, right, output) = (
+            scratch.path("left.parquet"),
+            scratch.path("right.parquet"),
+            scratch.path("output.parquet"),
+        );
+        let (left, right, output) = (left.as_str(), right.as_str(), output.as_str());
+        write_input(
+            left,
+            "message schema { REQUIRED INT64 account; REQUIRED INT32 amount; }",
+            &[(1, 10), (2, 20), (2, 21), (4, 40)],
+        );
+        write_input(
+            right,
+            "message schema { REQUIRED INT64 account_id; REQUIRED INT32 amount; }",
+            &[(2, 200), (2, 201), (3, 300)],
+        );
+        merge_join(left, right, "account", "account_id", join_type, output);
+
+        let message_type = match join_type {
+            JoinType::Inner => "message schema { REQUIRED INT64 account; REQUIRED INT32 amount; REQUIRED INT64 account_id; REQUIRED INT32 amount_right; }",
+            _ => "message schema { OPTIONAL INT64 account; OPTIONAL INT32 amount; OPTIONAL INT64 account_id; OPTIONAL INT32 amount_right; }",
+        };
+        read_rows(output, None, message_type)
+            .iter()
+            .map(|row| {
+                (
+                    row.get_long(0).unwrap_or(-1),
+                    row.get_int(1).unwrap_or(-1),
+                    row.get_long(2).unwrap_or(-1),
+                    row.get_int(3).unwrap_or(-1),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_join() {
+        let matches = vec![(2, 20, 2, 200), (2, 20, 2, 201), (2, 21, 2, 200), (2, 21, 2, 201)];
+        assert_eq!(join(JoinType::Inner), matches);
+
+        let full = join(JoinType::Full);
+        assert_eq!(full.len(), 7);
+        assert_eq!(full[0], (1, 10, -1, -1));
+        assert_eq!(full[5], (-1, -1, 3, 300));
+        assert_eq!(full[6], (4, 40, -1, -1));
+    }
+}
@@ -0,0 +1,363 @@
+use crate::metadata::get_message_type;
+use crate::parquet_reader::get_parquet_reader;
+use crate::projection::copy_column;
+use crate::rowwritebuffer::create_row;
+use parquet::{
+    basic::{ConvertedType, Repetition},
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::sync::Arc;
+
+/// How the schemas of the inputs of a merge are reconciled to the schema of the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SchemaStrategy {
+    /// All inputs should have the same columns with the same types, although the order of the columns may differ.
+    /// Otherwise the merge fails with a diff of the schemas.
+    #[default]
+    Strict,
+    /// The output contains the columns of all inputs. A column that is missing in an input is null for the rows of that
+    /// input, so such a column becomes optional.
+    Union,
+    /// The output only contains the columns that occur in all inputs.
+    Intersect,
+}
+
+/// The way the rows of an input are mapped to the output schema.
+#[derive(Clone)]
+pub struct InputMapping {
+    /// The projection that reads the columns of the input that occur in the output, in the order of the output.
+    pub message_type: String,
+    /// The positions (in the output) of the columns that are missing in the input, in increasing order.
+    missing: Vec<(usize, String)>,
+}
+
+impl InputMapping {
+    /// True if the projected rows need to be extended with null-columns.
+    pub fn needs_mapping(&self) -> bool {
+        !self.missing.is_empty()
+    }
+
+    /// Insert the nulls for the missing columns in a (projected) row of this input.
+    pub fn map_row(&self, row: Row) -> Row {
+        let mut columns: Vec<(String, Field)> = row
+            .get_column_iter()
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect();
+        self.missing
+            .iter()
+            .for_each(|(pos, name)| columns.insert(*pos, (name.clone(), Field::Null)));
+        create_row(columns)
+    }
+}
+
+/// The schema of the output of a merge, and the mapping of each of the inputs to this schema.
+pub struct Reconciliation {
+    pub schema: Arc<Type>,
+    pub inputs: Vec<InputMapping>,
+}
+
+/// Get the schemas of the files and reconcile these according to 'strategy'. With a 'selection' only these columns are
+/// read from the inputs (see 'reconcile_schemas').
+pub fn reconcile_files(paths: &[&str], strategy: SchemaStrategy, selection: Option<&[String]>) -> Reconciliation {
+    let schemas: Vec<Type> = paths
+        .iter()
+        .map(|path| get_parquet_reader(path).metadata().file_metadata().schema().clone())
+        .collect();
+    reconcile_schemas(paths, &schemas, strategy, selection)
+}
+
+fn find_column<'a>(schema: &'a Type, name: &str) -> Option<&'a Arc<Type>> {
+    schema
+        .get_fields()
+        .iter()
+        .find(|field| field.get_basic_info().name() == name)
+}
+
+/// Short description of the type of a column, as used in the schema-diff.
+fn describe_column(column: &Type) -> String {
+    let basic_info = column.get_basic_info();
+    let repetition = basic_info.repetition();
+    let converted_type = match basic_info.converted_type() {
+        ConvertedType::NONE => "".to_owned(),
+        other => format!(" ({other})"),
+    };
+    if column.is_primitive() {
+        format!("{repetition} {}{converted_type}", column.get_physical_type())
+    } else {
+        let fields: Vec<String> = column
+            .get_fields()
+            .iter()
+            .map(|field| format!("{}: {}", field.get_basic_info().name(), describe_column(field)))
+            .collect();
+        format!("{repetition} group{converted_type} {{ {} }}", fields.join(", "))
+    }
+}
+
+/// Check whether the values of 'other' can be written as values of 'column'. The physical, converted and logical types
+/// should be the same, and a group should have the same fields with the same repetition. Only the repetition of the
+/// column itself may differ between required and optional, as the reconciled column becomes optional.
+fn is_compatible(column: &Type, other: &Type) -> bool {
+    let (basic_info, other_info) = (column.get_basic_info(), other.get_basic_info());
+    if (basic_info.repetition() == Repetition::REPEATED) != (other_info.repetition() == Repetition::REPEATED)
+        || basic_info.converted_type() != other_info.converted_type()
+        || basic_info.logical_type() != other_info.logical_type()
+    {
+        return false;
+    }
+    match (column, other) {
+        (
+            Type::PrimitiveType {
+                physical_type,
+                type_length,
+                scale,
+                precision,
+                ..
+            },
+            Type::PrimitiveType {
+                physical_type: other_physical_type,
+                type_length: other_type_length,
+                scale: other_scale,
+                precision: other_precision,
+                ..
+            },
+        ) => {
+            physical_type == other_physical_type
+                && type_length == other_type_length
+                && scale == other_scale
+                && precision == other_precision
+        }
+        (Type::GroupType { fields, .. }, Type::GroupType { fields: other_fields, .. }) => {
+            fields.len() == other_fields.len()
+                && fields.iter().zip(other_fields).all(|(field, other_field)| {
+                    field.get_basic_info().name() == other_field.get_basic_info().name()
+                        && field.get_basic_info().repetition() == other_field.get_basic_info().repetition()
+                        && is_compatible(field, other_field)
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Copy of 'column' that accepts nulls.
+pub fn make_optional(column: &Type) -> Type {
+    let basic_info = column.get_basic_info();
+    if basic_info.repetition() != Repetition::REQUIRED {
+        return column.clone();
+    }
+    copy_column(column, basic_info.name(), Repetition::OPTIONAL)
+}
+
+/// Describe the differences between the schema of input 'idx' and the schema of the first input.
+fn schema_diff(paths: &[&str], schemas: &[Type], idx: usize) -> Vec<String> {
+    let (first, other) = (&schemas[0], &schemas[idx]);
+    let (first_path, other_path) = (paths[0], paths[idx]);
+
+    let mut diff: Vec<String> = first
+        .get_fields()
+        .iter()
+        .filter_map(|column| {
+            let name = column.get_basic_info().name();
+            match find_column(other, name) {
+                None => Some(format!("'{other_path}' lacks column '{name}' ({})", describe_column(column))),
+                Some(other_column) if other_column != column => Some(format!(
+                    "column '{name}' is {} in '{first_path}' and {} in '{other_path}'",
+                    describe_column(column),
+                    describe_column(other_column)
+                )),
+                Some(_) => None,
+            }
+        })
+        .collect();
+    other
+        .get_fields()
+        .iter()
+        .filter(|column| find_column(first, column.get_basic_info().name()).is_none())
+        .for_each(|column| {
+            diff.push(format!(
+                "'{other_path}' has an extra column '{}' ({})",
+                column.get_basic_info().name(),
+                describe_column(column)
+            ))
+        });
+    diff
+}
+
+/// Determine the output schema for the 'schemas' of the inputs 'paths' according to 'strategy'. The columns of the output
+/// follow the order of the first input, and for a union the extra columns of later inputs are appended.
+/// With a 'selection' the output only contains the selected columns (still in the order described above), such that the
+/// other columns are not read. The strategy still applies to the full schemas, so a strict merge also fails on a difference
+/// in a column that is not selected.
+/// Panics when the schemas can not be reconciled, which is the case for columns with the same name but a different type
+/// (see 'is_compatible').
+pub fn reconcile_schemas(
+    paths: &[&str],
+    schemas: &[Type],
+    strategy: SchemaStrategy,
+    selection: Option<&[String]>,
+) -> Reconciliation {
+    assert!(!schemas.is_empty(), "Nothing to reconcile");
+
+    if strategy == SchemaStrategy::Strict {
+        let diff: Vec<String> = (1..schemas.len())
+            .flat_map(|idx| schema_diff(paths, schemas, idx))
+            .collect();
+        if !diff.is_empty() {
+            panic!(
+                "The schemas of the inputs differ (use SchemaStrategy::Union or SchemaStrategy::Intersect to merge these):\n  {}",
+                diff.join("\n  ")
+            );
+        }
+    }
+
+    // collect the candidate columns in output order
+    let mut names: Vec<&str> = Vec::new();
+    schemas.iter().for_each(|schema| {
+        schema.get_fields().iter().for_each(|column| {
+            let name = column.get_basic_info().name();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        })
+    });
+    if let Some(selection) = selection {
+        if let Some(unknown) = selection.iter().find(|selected| !names.contains(&selected.as_str())) {
+            panic!("Column '{unknown}' does not occur in any of the inputs");
+        }
+        names.retain(|name| selection.iter().any(|selected| selected == name));
+    }
+
+    let mut columns: Vec<Arc<Type>> = Vec::new();
+    names.into_iter().for_each(|name| {
+        let present: Vec<&Arc<Type>> = schemas.iter().filter_map(|schema| find_column(schema, name)).collect();
+        let in_all = present.len() == schemas.len();
+        if strategy == SchemaStrategy::Intersect && !in_all {
+            return;
+        }
+        let column = present[0];
+        if let Some(conflict) = present.iter().find(|other| !is_compatible(column, other)) {
+            panic!(
+                "Column '{name}' can not be reconciled, as it is {} in one input and {} in another",
+                describe_column(column),
+                describe_column(conflict)
+            );
+        }
+        let nullable = !in_all || present.iter().any(|other| other.get_basic_info().repetition() != Repetition::REQUIRED);
+        columns.push(if nullable {
+            Arc::new(make_optional(column))
+        } else {
+            Arc::clone(column)
+        });
+    });
+
+    let schema = Type::group_type_builder(schemas[0].name())
+        .with_fields(&mut columns.clone())
+        .build()
+        .unwrap();
+
+    let inputs = schemas
+        .iter()
+        .map(|input_schema| {
+            // the projection uses the column-types of the input itself, as a projection has to match the file
+            let mut projected: Vec<Arc<Type>> = Vec::new();
+            let mut missing = Vec::new();
+            columns.iter().enumerate().for_each(|(pos, column)| {
+                let name = column.get_basic_info().name();
+                match find_column(input_schema, name) {
+                    Some(input_column) => projected.push(Arc::clone(input_column)),
+                    None => missing.push((pos, name.to_owned())),
+                }
+            });
+            let projection = Type::group_type_builder(input_schema.name())
+                .with_fields(&mut projected)
+                .build()
+                .unwrap();
+            InputMapping {
+                message_type: get_message_type(&projection),
+                missing,
+            }
+        })
+        .collect();
+
+    Reconciliation {
+        schema: Arc::new(schema),
+        inputs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconcile_schemas, SchemaStrategy};
+    use crate::rowwritebuffer::create_row;
+    use parquet::{
+        basic::Repetition,
+        record::{Field, RowAccessor},
+        schema::{parser::parse_message_type, types::Type},
+    };
+
+    fn test_schemas() -> Vec<Type> {
+        let first = "message schema {
+            REQUIRED INT64 id;
+            REQUIRED BINARY account (UTF8);
+        }";
+        let second = "message schema {
+            REQUIRED BINARY account (UTF8);
+            REQUIRED INT64 id;
+            REQUIRED INT32 amount;
+        }";
+        vec![parse_message_type(first).unwrap(), parse_message_type(second).unwrap()]
+    }
+
+    fn column_names(schema: &Type) -> Vec<&str> {
+        schema.get_fields().iter().map(|f| f.get_basic_info().name()).collect()
+    }
+
+    #[test]
+    fn test_union_and_intersect() {
+        let paths = ["first", "second"];
+        let schemas = test_schemas();
+
+        let union = reconcile_schemas(&paths, &schemas, SchemaStrategy::Union, None);
+        assert_eq!(column_names(&union.schema), vec!["id", "account", "amount"]);
+        assert_eq!(union.schema.get_fields()[0].get_basic_info().repetition(), Repetition::REQUIRED);
+        assert_eq!(union.schema.get_fields()[2].get_basic_info().repetition(), Repetition::OPTIONAL);
+
+        // the first input lacks 'amount', so a null is added
+        assert!(union.inputs[0].needs_mapping());
+        assert!(!union.inputs[1].needs_mapping());
+        let row = create_row(vec![
+            ("id".to_owned(), Field::Long(1)),
+            ("account".to_owned(), Field::Str("abc".to_owned())),
+        ]);
+        let mapped = union.inputs[0].map_row(row);
+        assert_eq!(mapped.len(), 3);
+        assert_eq!(mapped.get_long(0).unwrap(), 1);
+        assert!(mapped.get_int(2).is_err());
+
+        let intersect = reconcile_schemas(&paths, &schemas, SchemaStrategy::Intersect, None);
+        assert_eq!(column_names(&intersect.schema), vec!["id", "account"]);
+        assert!(!intersect.inputs[1].needs_mapping());
+        let projection = parse_message_type(&intersect.inputs[1].message_type).unwrap();
+        assert_eq!(column_names(&projection), vec!["id", "account"]);
+
+        let selection = vec!["amount".to_owned(), "id".to_owned()];
+        let selected = reconcile_schemas(&paths, &schemas, SchemaStrategy::Union, Some(&selection));
+        assert_eq!(column_names(&selected.schema), vec!["id", "amount"]);
+        let projection = parse_message_type(&selected.inputs[1].message_type).unwrap();
+        assert_eq!(column_names(&projection), vec!["id", "amount"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Column 'day' can not be reconciled")]
+    fn test_union_rejects_other_logical_type() {
+        let first = parse_message_type("message schema { REQUIRED INT32 day (DATE); }").unwrap();
+        let second = parse_message_type("message schema { REQUIRED INT32 day; }").unwrap();
+        reconcile_schemas(&["first", "second"], &[first, second], SchemaStrategy::Union, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "'second' has an extra column 'amount'")]
+    fn test_strict_reports_diff() {
+        reconcile_schemas(&["first", "second"], &test_schemas(), SchemaStrategy::Strict, None);
+    }
+}
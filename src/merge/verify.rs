@@ -0,0 +1,107 @@
+use crate::{
+    find_field,
+    parquet_reader::get_parquet_reader,
+    statistics::{row_group_min_max, StatValue},
+};
+use parquet::record::Row;
+use std::{cmp::Ordering, sync::Arc};
+
+/// Cheap check, based on the row-group statistics, that the row-groups of 'path' are ordered on column 'key': the max of a
+/// row-group should not exceed the min of the next row-group (or the reverse when 'descending'). Row-groups without
+/// statistics are skipped. Rows that are out of order within a row-group are only detected by the 'InputVerifier'.
+pub fn check_row_group_order(path: &str, key: &str, descending: bool) {
+    let reader = get_parquet_reader(path);
+    let metadata = reader.metadata();
+    let (col, _) = find_field(Arc::new(metadata.file_metadata().schema().clone()), key);
+
+    let ranges: Vec<Option<(StatValue, StatValue)>> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group_min_max(row_group, col))
+        .collect();
+    ranges.windows(2).enumerate().for_each(|(idx, pair)| {
+        if let (Some((min, max)), Some((next_min, next_max))) = (&pair[0], &pair[1]) {
+            let out_of_order = if descending {
+                min.compare(next_max) == Some(Ordering::Less)
+            } else {
+                max.compare(next_min) == Some(Ordering::Greater)
+            };
+            if out_of_order {
+                panic!(
+                    "Input '{path}' is not sorted on '{key}': row-group {idx} has range [{min:?}, {max:?}] while row-group {} has range [{next_min:?}, {next_max:?}]",
+                    idx + 1
+                );
+            }
+        }
+    });
+}
+
+/// Tracks the position in an input of a merge, such that a row that is out of order can be reported with its file,
+/// row-group and row number.
+pub struct InputVerifier {
+    path: String,
+    row_group_ends: Vec<u64>,
+    row: u64,
+}
+
+impl InputVerifier {
+    pub fn new(path: &str) -> Self {
+        let row_group_ends = get_parquet_reader(path)
+            .metadata()
+            .row_groups()
+            .iter()
+            .scan(0, |end, row_group| {
+                *end += row_group.num_rows() as u64;
+                Some(*end)
+            })
+            .collect();
+        Self {
+            path: path.to_owned(),
+            row_group_ends,
+            row: 0,
+        }
+    }
+
+    /// The row-group and the row within that row-group of row 'row' of the file.
+    fn position(&self, row: u64) -> (usize, u64) {
+        let row_group = self
+            .row_group_ends
+            .iter()
+            .position(|end| row < *end)
+            .unwrap_or(self.row_group_ends.len());
+        let start = if row_group == 0 { 0 } else { self.row_group_ends[row_group - 1] };
+        (row_group, row - start)
+    }
+
+    /// Register that the input advanced from row 'prev' to row 'next'. Panics if 'next' sorts before 'prev'.
+    pub fn check<F>(&mut self, prev: &Row, next: &Row, smaller: &F)
+    where
+        F: Fn(&Row, &Row) -> bool,
+    {
+        self.row += 1;
+        if !smaller(prev, next) {
+            let (row_group, row_in_group) = self.position(self.row);
+            panic!(
+                "Input '{}' is not sorted: row {row_in_group} of row-group {row_group} (row {} of the file) sorts before the previous row",
+                self.path, self.row
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputVerifier;
+
+    #[test]
+    fn test_position() {
+        let verifier = InputVerifier {
+            path: "test".to_owned(),
+            row_group_ends: vec![3, 5, 9],
+            row: 0,
+        };
+        assert_eq!(verifier.position(0), (0, 0));
+        assert_eq!(verifier.position(3), (1, 0));
+        assert_eq!(verifier.position(8), (2, 3));
+    }
+}
@@ -1,4 +1,8 @@
-use parquet::{basic::ConvertedType, file::metadata::ParquetMetaData, schema::types::Type};
+use parquet::{
+    basic::ConvertedType,
+    file::metadata::ParquetMetaData,
+    schema::{printer, types::Type},
+};
 use std::{any::type_name, sync::Arc};
 
 use crate::parquet_reader::get_parquet_reader;
@@ -98,6 +102,13 @@ fn find_field_aux(group: Arc<Type>, field_name: &str, state: FFAResType) -> FFAR
         })
 }
 
+/// Format a schema as a message-type, which can be used as a projection when reading a file.
+pub fn get_message_type(schema: &Type) -> String {
+    let mut message_type = Vec::new();
+    printer::print_schema(&mut message_type, schema);
+    String::from_utf8(message_type).unwrap()
+}
+
 // Find a field in the schema based on the names. For nested names we do not parse the full path, but only the name of the leaf.
 // In case the field_name occurs multiple times, for example in nested fields or when the field_name does not exist the function panics.
 pub fn find_field(schema: Arc<Type>, field_name: &str) -> FFResType {
@@ -110,9 +121,22 @@ pub fn find_field(schema: Arc<Type>, field_name: &str) -> FFResType {
     }
 }
 
+/// Find a top-level primitive column of the schema. Returns its position among the top-level columns, which is the position
+/// of its field in a 'Row' (whereas 'find_field' returns the index of the leaf, as used for the column-chunks of a row-group).
+pub fn find_column(schema: Arc<Type>, column_name: &str) -> FFResType {
+    let position = schema
+        .get_fields()
+        .iter()
+        .position(|column| column.name() == column_name)
+        .unwrap_or_else(|| panic!("Failed to find a top-level column with name: '{column_name}'"));
+    let column = Arc::clone(&schema.get_fields()[position]);
+    assert!(column.is_primitive(), "Column '{column_name}' should be a primitive column");
+    (position, column)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::metadata::find_field;
+    use crate::metadata::{find_column, find_field};
     use parquet::schema::parser::parse_message_type;
     use std::sync::Arc;
 
@@ -163,4 +187,21 @@ mod tests {
         println!("the selected type = {tpe:?}");
         assert_eq!(idx, 3);
     }
+
+    #[test]
+    fn test_find_column_nested() {
+        let msg_type = "
+        message schema {
+            REQUIRED GROUP nested_rec {
+                REQUIRED BINARY account (UTF8);
+                REQUIRED INT32 amount;
+            }
+            REQUIRED INT64 datetime (TIMESTAMP(MILLIS,true));
+        }";
+        let schema = Arc::new(parse_message_type(msg_type).unwrap());
+
+        // the leaf-index counts the leaves of the group, the position only the top-level columns.
+        assert_eq!(find_field(Arc::clone(&schema), "datetime").0, 2);
+        assert_eq!(find_column(schema, "datetime").0, 1);
+    }
 }
@@ -0,0 +1,41 @@
+use async_bridge;
+use std::{fs, io, sync::OnceLock};
+
+/// The S3-client that is shared by all removals, as loading the configuration from the environment is costly and a
+/// scratch space removes its objects one by one.
+fn s3_client() -> &'static aws_sdk_s3::Client {
+    static S3_CLIENT: OnceLock<aws_sdk_s3::Client> = OnceLock::new();
+    S3_CLIENT.get_or_init(|| {
+        async_bridge::run_async(async {
+            let config = aws_config::load_from_env().await;
+            aws_sdk_s3::Client::new(&config)
+        })
+    })
+}
+
+/// Remove the file or object represented by 'path'.
+/// If string is prefixed by 's3:' it will be a s3-object. Otherswise it will be a path on the local file system.
+pub fn remove_object(path: &str) -> io::Result<()> {
+    match path.split(':').next().unwrap() {
+        prefix if path.len() == prefix.len() => fs::remove_file(path),
+        "mem" =>panic!("prefix 'mem:'can best be handled via temp-files, or all data should be incoded in the path-string"),
+        "s3" => {
+            let parts: Vec<&str> = path.split(":").collect();
+            assert_eq!(parts.len(), 3, "Path should have format \"s3:<bucket>:<object_name>\".");
+            let bucket_name = parts[1].to_string();
+            let object_name = parts[2].to_owned();
+            let client = s3_client();
+            async_bridge::run_async(async {
+                client
+                    .delete_object()
+                    .bucket(bucket_name)
+                    .key(object_name)
+                    .send()
+                    .await
+            })
+            .map(|_| ())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("Failed to remove {path}: {err}")))
+        }
+        prefix => panic!("remove_object not implemented for prefix {prefix} of path {path}")
+        }
+}
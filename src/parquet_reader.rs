@@ -1,10 +1,14 @@
 use parquet::file::{
-    metadata::ParquetMetaData,
+    metadata::{ParquetMetaData, RowGroupMetaData},
     reader::{FileReader, SerializedFileReader},
+    serialized_reader::ReadOptionsBuilder,
 };
 use s3_file::S3Reader;
 use std::fs::File;
 
+/// Predicate that decides on the metadata and the index of a row-group whether it should be read.
+pub type RowGroupPredicate = Box<dyn FnMut(&RowGroupMetaData, usize) -> bool>;
+
 pub enum ParquetReaderEnum {
     File(SerializedFileReader<File>),
     S3(SerializedFileReader<S3Reader>),
@@ -22,17 +26,38 @@ impl ParquetReaderEnum {
     pub fn num_rows(&self) -> i64 {
         self.metadata().file_metadata().num_rows()
     }
+
+    /// Access the reader as a FileReader, for example to read individual row-groups.
+    pub fn file_reader(&self) -> &dyn FileReader {
+        match self {
+            Self::File(reader) => reader,
+            Self::S3(reader) => reader,
+        }
+    }
 }
 
 /// Create an iterator over the data of a Parquet-file or Parquet S3 object 
 /// If string is prefixed by 'mem:' this will be an in memory buffer, if is is prefixed by 's3:' it will be a s3-object. Otherswise it will be a path on the local file system.
 pub fn get_parquet_reader<'a>(path: &'a str) -> ParquetReaderEnum {
+    get_parquet_reader_filtered(path, None)
+}
+
+/// Create a reader like 'get_parquet_reader', but only expose the row-groups accepted by the 'predicate'.
+/// The file-metadata (including num_rows) still describes the full file, the row-group metadata only contains the selected row-groups.
+pub fn get_parquet_reader_filtered<'a>(path: &'a str, predicate: Option<RowGroupPredicate>) -> ParquetReaderEnum {
+    let options = match predicate {
+        Some(predicate) => ReadOptionsBuilder::new().with_predicate(predicate),
+        None => ReadOptionsBuilder::new(),
+    }
+    .build();
+
     // we differentiate at this level for the different types of inputs as lower levels can not handle this more generic
     // as the Associated types are in the way on ChunkReader, and also on SerializedFileReader as it wants to see the generic.
     // handling it at this level introduces some source-code-duplication, but that is manageable.
     match path.split(':').next().unwrap() {
         prefix if path.len() == prefix.len() => { // no prefix, so it is a file
-                let reader = SerializedFileReader::try_from(path.to_owned()).unwrap();
+                let file = File::open(path).expect(&format!("Failed to open file {}", path));
+                let reader = SerializedFileReader::new_with_options(file, options).unwrap();
                 ParquetReaderEnum::File(reader)
             }
         "mem" =>panic!("prefix 'mem:'can best be handled via temp-files, or all data should be incoded in the path-string"),
@@ -43,7 +68,7 @@ pub fn get_parquet_reader<'a>(path: &'a str) -> ParquetReaderEnum {
             let object_name = parts[2].to_owned();
             let chunk_reader = S3Reader::new(bucket_name, object_name, 10_000*1024);
 
-            let reader = SerializedFileReader::new(chunk_reader).unwrap();
+            let reader = SerializedFileReader::new_with_options(chunk_reader, options).unwrap();
             ParquetReaderEnum::S3(reader)
         }
         prefix => panic!("get_parquet_iter not implemented for prefix {prefix} of path {path}")
@@ -1,6 +1,6 @@
 use parquet::{
     basic::Compression,
-    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    file::{metadata::KeyValue, properties::WriterProperties, writer::SerializedFileWriter},
     schema::types::Type,
 };
 use s3_file::S3Writer;
@@ -12,11 +12,13 @@ pub enum ParquetWriter {
 }
 
 /// Parse the string and return a ParquetWriter with the corresponding type.
-pub fn get_parquet_writer(path: &str, schema: Arc<Type>) -> ParquetWriter {
+/// The 'key_value_metadata' is stored in the footer of the file.
+pub fn get_parquet_writer(path: &str, schema: Arc<Type>, key_value_metadata: Option<Vec<KeyValue>>) -> ParquetWriter {
     // TODO: at this location we are still tightly lined to the test-types (ttypes)
     let props = Arc::new(
         WriterProperties::builder()
             .set_compression(Compression::SNAPPY)
+            .set_key_value_metadata(key_value_metadata)
             .build(),
     );
 
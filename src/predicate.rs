@@ -0,0 +1,335 @@
+use crate::parquet_reader::RowGroupPredicate;
+use crate::statistics::{row_group_min_max, StatValue};
+use parquet::{
+    basic::Type as PhysicalType,
+    file::metadata::RowGroupMetaData,
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::cmp::Ordering;
+
+/// The comparison of a column with a value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    /// True if a value with 'ordering' relative to the literal satisfies the comparison.
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+
+    /// True if a row-group with column-values in [min, max] can contain a value that satisfies the comparison with 'value'.
+    /// If the values can not be compared the row-group is kept.
+    fn may_hold(self, min: &StatValue, max: &StatValue, value: &StatValue) -> bool {
+        match (min.compare(value), max.compare(value)) {
+            (Some(min_ord), Some(max_ord)) => match self {
+                CompareOp::Eq => min_ord != Ordering::Greater && max_ord != Ordering::Less,
+                CompareOp::Lt | CompareOp::Le => self.holds(min_ord),
+                CompareOp::Gt | CompareOp::Ge => self.holds(max_ord),
+            },
+            _ => true,
+        }
+    }
+}
+
+/// A value that can be compared with a column in a 'Predicate'.
+pub trait Literal {
+    fn into_field(self) -> Field;
+}
+
+impl Literal for Field {
+    fn into_field(self) -> Field {
+        self
+    }
+}
+
+impl Literal for &str {
+    fn into_field(self) -> Field {
+        Field::Str(self.to_owned())
+    }
+}
+
+impl Literal for String {
+    fn into_field(self) -> Field {
+        Field::Str(self)
+    }
+}
+
+impl Literal for i32 {
+    fn into_field(self) -> Field {
+        Field::Int(self)
+    }
+}
+
+impl Literal for i64 {
+    fn into_field(self) -> Field {
+        Field::Long(self)
+    }
+}
+
+impl Literal for f64 {
+    fn into_field(self) -> Field {
+        Field::Double(self)
+    }
+}
+
+impl Literal for bool {
+    fn into_field(self) -> Field {
+        Field::Bool(self)
+    }
+}
+
+/// A condition on the (top-level) columns of a row, for example 'col("account").eq("abcdef")'.
+/// A null never satisfies a comparison or an IN-list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    Compare { column: String, op: CompareOp, value: Field },
+    In { column: String, values: Vec<Field> },
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+/// A column of which the values are compared in a 'Predicate'.
+#[derive(Clone, Debug)]
+pub struct Column(String);
+
+/// Refer to the column 'name' in a 'Predicate'.
+pub fn col(name: &str) -> Column {
+    Column(name.to_owned())
+}
+
+impl Column {
+    fn compare<V: Literal>(self, op: CompareOp, value: V) -> Predicate {
+        Predicate::Compare {
+            column: self.0,
+            op,
+            value: value.into_field(),
+        }
+    }
+
+    pub fn eq<V: Literal>(self, value: V) -> Predicate {
+        self.compare(CompareOp::Eq, value)
+    }
+
+    pub fn lt<V: Literal>(self, value: V) -> Predicate {
+        self.compare(CompareOp::Lt, value)
+    }
+
+    pub fn le<V: Literal>(self, value: V) -> Predicate {
+        self.compare(CompareOp::Le, value)
+    }
+
+    pub fn gt<V: Literal>(self, value: V) -> Predicate {
+        self.compare(CompareOp::Gt, value)
+    }
+
+    pub fn ge<V: Literal>(self, value: V) -> Predicate {
+        self.compare(CompareOp::Ge, value)
+    }
+
+    /// The values in the range [low, high], so including both bounds.
+    pub fn between<V: Literal>(self, low: V, high: V) -> Predicate {
+        self.clone().ge(low).and(self.le(high))
+    }
+
+    pub fn is_in<V: Literal>(self, values: Vec<V>) -> Predicate {
+        Predicate::In {
+            column: self.0,
+            values: values.into_iter().map(Literal::into_field).collect(),
+        }
+    }
+}
+
+/// Map a literal to the domain of the statistics.
+fn stat_value(column: &str, value: &Field) -> StatValue {
+    StatValue::from_field(value).unwrap_or_else(|| panic!("Column '{column}' can not be compared with {value:?}"))
+}
+
+/// Map a literal to the domain of the statistics, and check that it can be compared with the values of column 'field'.
+/// Otherwise a literal of another type, like a string for a numeric column, would silently filter out all rows.
+pub(crate) fn checked_value(field: &Type, value: &Field) -> StatValue {
+    let column = field.name();
+    let stat = stat_value(column, value);
+    let comparable = field.is_primitive()
+        && matches!(
+            (field.get_physical_type(), &stat),
+            (PhysicalType::BOOLEAN, StatValue::Bool(_))
+                | (PhysicalType::INT32 | PhysicalType::INT64, StatValue::Int(_) | StatValue::UInt(_))
+                | (PhysicalType::FLOAT | PhysicalType::DOUBLE, StatValue::Float(_))
+                | (PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY, StatValue::Bytes(_))
+        );
+    if !comparable {
+        let column_type = if field.is_primitive() { field.get_physical_type().to_string() } else { "group".to_owned() };
+        panic!("Column '{column}' of type {column_type} can not be compared with {value:?}");
+    }
+    stat
+}
+
+impl Predicate {
+    pub fn and(self, other: Predicate) -> Predicate {
+        Predicate::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Predicate) -> Predicate {
+        Predicate::Or(Box::new(self), Box::new(other))
+    }
+
+    /// False if the min/max statistics of 'row_group' show that none of its rows satisfies the predicate. A row-group
+    /// without statistics for a column (or where all values are null) is kept.
+    pub fn may_match(&self, row_group: &RowGroupMetaData) -> bool {
+        let min_max = |column: &str| {
+            row_group
+                .columns()
+                .iter()
+                .position(|chunk| chunk.column_descr().name() == column)
+                .and_then(|col| row_group_min_max(row_group, col))
+        };
+        match self {
+            Predicate::Compare { column, op, value } => match min_max(column) {
+                Some((min, max)) => op.may_hold(&min, &max, &stat_value(column, value)),
+                None => true,
+            },
+            Predicate::In { column, values } => match min_max(column) {
+                Some((min, max)) => values
+                    .iter()
+                    .any(|value| CompareOp::Eq.may_hold(&min, &max, &stat_value(column, value))),
+                None => true,
+            },
+            Predicate::And(left, right) => left.may_match(row_group) && right.may_match(row_group),
+            Predicate::Or(left, right) => left.may_match(row_group) || right.may_match(row_group),
+        }
+    }
+
+    /// The predicate as a 'RowGroupPredicate', such that a reader skips the row-groups that can not match.
+    pub(crate) fn row_group_predicate(&self) -> RowGroupPredicate {
+        let predicate = self.clone();
+        Box::new(move |row_group: &RowGroupMetaData, _: usize| predicate.may_match(row_group))
+    }
+
+    /// Resolve the columns of the predicate in rows with 'schema'. Panics if a column is not a top-level column of
+    /// 'schema', or if a value can not be compared with the values of its column.
+    pub(crate) fn bind(&self, schema: &Type) -> RowFilter {
+        let field = |column: &str| {
+            schema
+                .get_fields()
+                .iter()
+                .enumerate()
+                .find(|(_, field)| field.name() == column)
+                .unwrap_or_else(|| panic!("Column '{column}' of the predicate is not part of the rows that are read"))
+        };
+        match self {
+            Predicate::Compare { column, op, value } => {
+                let (pos, field) = field(column);
+                RowFilter::Compare {
+                    pos,
+                    op: *op,
+                    value: checked_value(field, value),
+                }
+            }
+            Predicate::In { column, values } => {
+                let (pos, field) = field(column);
+                RowFilter::In {
+                    pos,
+                    values: values.iter().map(|value| checked_value(field, value)).collect(),
+                }
+            }
+            Predicate::And(left, right) => RowFilter::And(Box::new(left.bind(schema)), Box::new(right.bind(schema))),
+            Predicate::Or(left, right) => RowFilter::Or(Box::new(left.bind(schema)), Box::new(right.bind(schema))),
+        }
+    }
+}
+
+/// A 'Predicate' of which the columns are resolved to their position in the rows.
+pub(crate) enum RowFilter {
+    Compare { pos: usize, op: CompareOp, value: StatValue },
+    In { pos: usize, values: Vec<StatValue> },
+    And(Box<RowFilter>, Box<RowFilter>),
+    Or(Box<RowFilter>, Box<RowFilter>),
+}
+
+impl RowFilter {
+    pub fn matches(&self, row: &Row) -> bool {
+        let ordering = |pos: usize, value: &StatValue| {
+            row.get_column_iter()
+                .nth(pos)
+                .and_then(|(_, field)| StatValue::from_field(field))
+                .and_then(|field| field.compare(value))
+        };
+        match self {
+            RowFilter::Compare { pos, op, value } => ordering(*pos, value).map_or(false, |ord| op.holds(ord)),
+            RowFilter::In { pos, values } => values
+                .iter()
+                .any(|value| ordering(*pos, value) == Some(Ordering::Equal)),
+            RowFilter::And(left, right) => left.matches(row) && right.matches(row),
+            RowFilter::Or(left, right) => left.matches(row) || right.matches(row),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::col;
+    use crate::parquet_reader::get_parquet_reader;
+    use crate::rowiterext::read_rows_where;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    fn ids(rows: Vec<Row>) -> Vec<i64> {
+        rows.iter().map(|row| row.get_long(0).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_predicate_pushdown() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = scratch.path("predicate_pushdown.parquet");
+        let path = path.as_str();
+        let message_type = "message schema { REQUIRED INT64 id; REQUIRED BINARY account (UTF8); }";
+        let schema = Arc::new(parse_message_type(message_type).unwrap());
+        let mut row_writer = RowWriteBuffer::new(path, schema, 10).unwrap();
+        (0..30).for_each(|id| {
+            row_writer.append_row(create_row(vec![
+                ("id".to_owned(), Field::Long(id)),
+                ("account".to_owned(), Field::Str(format!("account-{:02}", id % 7))),
+            ]))
+        });
+        row_writer.close();
+
+        let predicate = col("id").between(12, 15).or(col("id").eq(27));
+        let row_groups: Vec<bool> = get_parquet_reader(path)
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|row_group| predicate.may_match(row_group))
+            .collect();
+        assert_eq!(row_groups, vec![false, true, true]);
+
+        assert_eq!(ids(read_rows_where(path, None, message_type, &predicate)), vec![12, 13, 14, 15, 27]);
+
+        let predicate = col("account").is_in(vec!["account-03", "account-05"]).and(col("id").lt(12_i64));
+        assert_eq!(ids(read_rows_where(path, None, message_type, &predicate)), vec![3, 5, 10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Column 'id' of type INT64 can not be compared with Str(\"abc\")")]
+    fn test_bind_rejects_other_type() {
+        let schema = parse_message_type("message schema { REQUIRED INT64 id; }").unwrap();
+        col("id").eq("abc").bind(&schema);
+    }
+}
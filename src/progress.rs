@@ -0,0 +1,99 @@
+use std::{fmt, ops::Deref, sync::Arc, time::Duration};
+
+/// Receives the progress of the sort, merge and write operations. All methods have an empty default implementation,
+/// so an observer only implements the events it is interested in. The methods can be called from multiple threads
+/// (for example by the writer-thread of a 'RowWriteBuffer'), so an observer should use interior mutability to keep state.
+pub trait ProgressObserver: Send + Sync {
+    /// The operation that produces 'path' entered 'phase', for example "stage-1" of a multistage sort.
+    fn phase(&self, _path: &str, _phase: &str) {}
+
+    /// 'num_rows' more rows have been read from 'path'. The rows are reported in batches.
+    fn rows_read(&self, _path: &str, _num_rows: u64) {}
+
+    /// 'num_rows' more rows have been passed to the writer of 'path'.
+    fn rows_written(&self, _path: &str, _num_rows: u64) {}
+
+    /// Row-group 'row_group' of 'path' with 'num_rows' rows has been flushed, which took 'duration'.
+    fn row_group_flushed(&self, _path: &str, _row_group: usize, _num_rows: u64, _duration: Duration) {}
+
+    /// 'num_bytes' (compressed) bytes have been transferred to 'path'.
+    fn bytes_transferred(&self, _path: &str, _num_bytes: u64) {}
+
+    /// Informational message, for example on the algorithm that is selected.
+    fn message(&self, _message: &str) {}
+}
+
+/// Observer that ignores all events. This is the default observer.
+pub struct SilentObserver;
+
+impl ProgressObserver for SilentObserver {}
+
+/// Observer that reports all events on the console.
+pub struct ConsoleObserver;
+
+impl ProgressObserver for ConsoleObserver {
+    fn phase(&self, path: &str, phase: &str) {
+        println!("'{path}': entering phase {phase}");
+    }
+
+    fn rows_read(&self, path: &str, num_rows: u64) {
+        println!("'{path}': read {num_rows} rows");
+    }
+
+    fn rows_written(&self, path: &str, num_rows: u64) {
+        println!("'{path}': written {num_rows} rows");
+    }
+
+    fn row_group_flushed(&self, path: &str, row_group: usize, num_rows: u64, duration: Duration) {
+        println!("'{path}': flushing row-group {row_group} with {num_rows} rows takes {duration:?}");
+    }
+
+    fn bytes_transferred(&self, path: &str, num_bytes: u64) {
+        println!("'{path}': transferred {num_bytes} bytes");
+    }
+
+    fn message(&self, message: &str) {
+        println!("{message}");
+    }
+}
+
+/// Shared handle to a 'ProgressObserver', such that the observer can be passed as part of the options of an operation
+/// and handed to the threads of that operation. The default is the 'SilentObserver'.
+#[derive(Clone)]
+pub struct Progress(Arc<dyn ProgressObserver>);
+
+impl Progress {
+    pub fn new<O>(observer: O) -> Self
+    where
+        O: ProgressObserver + 'static,
+    {
+        Progress(Arc::new(observer))
+    }
+}
+
+impl From<Arc<dyn ProgressObserver>> for Progress {
+    /// Wrap an observer that is shared with the caller, for example to inspect its counters after the operation.
+    fn from(observer: Arc<dyn ProgressObserver>) -> Self {
+        Progress(observer)
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new(SilentObserver)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+impl Deref for Progress {
+    type Target = dyn ProgressObserver;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
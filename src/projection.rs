@@ -0,0 +1,215 @@
+use crate::{metadata::get_message_type, rowwritebuffer::create_row, sort_metadata::SortMetadata};
+use parquet::{
+    basic::Repetition,
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::sync::Arc;
+
+/// A column of the output of a merge or sort, which is written under 'rename' when set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputColumn {
+    pub name: String,
+    pub rename: Option<String>,
+}
+
+impl OutputColumn {
+    pub fn new(name: &str) -> Self {
+        OutputColumn {
+            name: name.to_owned(),
+            rename: None,
+        }
+    }
+
+    pub fn renamed(name: &str, output_name: &str) -> Self {
+        OutputColumn {
+            name: name.to_owned(),
+            rename: Some(output_name.to_owned()),
+        }
+    }
+
+    pub fn output_name(&self) -> &str {
+        self.rename.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Copy of 'column' with the 'name' and 'repetition'.
+pub fn copy_column(column: &Type, name: &str, repetition: Repetition) -> Type {
+    let basic_info = column.get_basic_info();
+    match column {
+        Type::PrimitiveType {
+            physical_type,
+            type_length,
+            scale,
+            precision,
+            ..
+        } => Type::primitive_type_builder(name, *physical_type)
+            .with_repetition(repetition)
+            .with_converted_type(basic_info.converted_type())
+            .with_logical_type(basic_info.logical_type())
+            .with_length(*type_length)
+            .with_precision(*precision)
+            .with_scale(*scale)
+            .build()
+            .unwrap(),
+        Type::GroupType { fields, .. } => Type::group_type_builder(name)
+            .with_repetition(repetition)
+            .with_converted_type(basic_info.converted_type())
+            .with_logical_type(basic_info.logical_type())
+            .with_fields(&mut fields.clone())
+            .build()
+            .unwrap(),
+    }
+}
+
+fn column_position(schema: &Type, name: &str) -> Option<usize> {
+    schema.get_fields().iter().position(|column| column.name() == name)
+}
+
+/// The projection that only reads the top-level columns 'names' of 'schema', where the columns keep the order of 'schema'.
+/// Panics if one of the 'names' is not a column of 'schema'.
+pub fn projection_message_type(schema: &Type, names: &[&str]) -> String {
+    names.iter().for_each(|name| {
+        if column_position(schema, name).is_none() {
+            panic!("Column '{name}' does not occur in the input");
+        }
+    });
+    let mut columns: Vec<Arc<Type>> = schema
+        .get_fields()
+        .iter()
+        .filter(|column| names.contains(&column.name()))
+        .cloned()
+        .collect();
+    let projection = Type::group_type_builder(schema.name())
+        .with_fields(&mut columns)
+        .build()
+        .unwrap();
+    get_message_type(&projection)
+}
+
+/// Maps rows to the 'OutputColumn's, which selects, reorders and renames the (top-level) columns of the rows.
+#[derive(Clone, Debug)]
+pub struct ColumnProjection {
+    /// The position of each output column in the input rows, with its output name.
+    columns: Vec<(usize, String)>,
+    schema: Arc<Type>,
+}
+
+impl ColumnProjection {
+    /// Projection of rows with 'schema' on the 'columns'. Panics on an unknown column or on a duplicate output name.
+    pub fn new(schema: &Type, columns: &[OutputColumn]) -> Self {
+        assert!(!columns.is_empty(), "The output should contain at least one column");
+
+        let mut output_columns: Vec<Arc<Type>> = Vec::new();
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let output_name = column.output_name();
+                assert!(
+                    output_columns.iter().all(|other| other.name() != output_name),
+                    "The output contains column '{output_name}' twice"
+                );
+                let pos = column_position(schema, &column.name)
+                    .unwrap_or_else(|| panic!("Column '{}' does not occur in the input", column.name));
+                let input_column = &schema.get_fields()[pos];
+                output_columns.push(Arc::new(copy_column(
+                    input_column,
+                    output_name,
+                    input_column.get_basic_info().repetition(),
+                )));
+                (pos, output_name.to_owned())
+            })
+            .collect();
+
+        let schema = Type::group_type_builder(schema.name())
+            .with_fields(&mut output_columns)
+            .build()
+            .unwrap();
+        ColumnProjection {
+            columns,
+            schema: Arc::new(schema),
+        }
+    }
+
+    /// The schema of the projected rows.
+    pub fn schema(&self) -> Arc<Type> {
+        Arc::clone(&self.schema)
+    }
+
+    pub fn map_row(&self, row: &Row) -> Row {
+        let fields: Vec<&Field> = row.get_column_iter().map(|(_, field)| field).collect();
+        create_row(
+            self.columns
+                .iter()
+                .map(|(pos, name)| (name.clone(), fields[*pos].clone()))
+                .collect(),
+        )
+    }
+
+    /// The sort-metadata of the projected rows, for rows that are sorted according to 'sort_metadata'. Returns None if
+    /// the sort-key is not part of the output, as the output is not sorted on one of its columns in that case.
+    pub fn sort_metadata(&self, sort_metadata: &SortMetadata) -> Option<SortMetadata> {
+        self.columns
+            .iter()
+            .position(|(pos, _)| *pos == sort_metadata.column)
+            .map(|column| SortMetadata {
+                key: self.columns[column].1.clone(),
+                column,
+                ..sort_metadata.clone()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{projection_message_type, ColumnProjection, OutputColumn};
+    use crate::{rowwritebuffer::create_row, sort_metadata::SortMetadata};
+    use parquet::{
+        record::{Field, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+
+    #[test]
+    fn test_column_projection() {
+        let schema = parse_message_type(
+            "message schema { REQUIRED INT64 id; REQUIRED BINARY account (UTF8); REQUIRED INT32 amount; }",
+        )
+        .unwrap();
+        let projection = ColumnProjection::new(
+            &schema,
+            &[OutputColumn::new("amount"), OutputColumn::renamed("id", "transaction_id")],
+        );
+        let names: Vec<&str> = projection.schema().get_fields().iter().map(|f| f.name()).collect();
+        assert_eq!(names, vec!["amount", "transaction_id"]);
+
+        let row = create_row(vec![
+            ("id".to_owned(), Field::Long(7)),
+            ("account".to_owned(), Field::Str("abc".to_owned())),
+            ("amount".to_owned(), Field::Int(12)),
+        ]);
+        let mapped = projection.map_row(&row);
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped.get_int(0).unwrap(), 12);
+        assert_eq!(mapped.get_long(1).unwrap(), 7);
+
+        let sort_metadata = SortMetadata {
+            key: "id".to_owned(),
+            column: 0,
+            descending: false,
+            algorithm: "simple".to_owned(),
+        };
+        let projected = projection.sort_metadata(&sort_metadata).unwrap();
+        assert_eq!((projected.key.as_str(), projected.column), ("transaction_id", 1));
+        assert!(projection
+            .sort_metadata(&SortMetadata {
+                column: 1,
+                ..sort_metadata
+            })
+            .is_none());
+
+        let message_type = projection_message_type(&schema, &["amount", "id"]);
+        let read_schema = parse_message_type(&message_type).unwrap();
+        let names: Vec<&str> = read_schema.get_fields().iter().map(|f| f.name()).collect();
+        assert_eq!(names, vec!["id", "amount"]);
+    }
+}
@@ -0,0 +1,75 @@
+use parquet::record::{Field, Row};
+use std::cmp::Ordering;
+
+/// Get the field at column 'col' of a row.
+pub fn get_field(row: &Row, col: usize) -> &Field {
+    row.get_column_iter()
+        .nth(col)
+        .expect(&format!("Row does not have a column {col}"))
+        .1
+}
+
+/// Compare two fields of the same (primitive) type. Null is smaller than any other value.
+/// Floating points are compared with a total ordering, such that sorting on these columns is well-defined.
+pub fn compare_fields(left: &Field, right: &Field) -> Ordering {
+    match (left, right) {
+        (Field::Null, Field::Null) => Ordering::Equal,
+        (Field::Null, _) => Ordering::Less,
+        (_, Field::Null) => Ordering::Greater,
+        (Field::Bool(l), Field::Bool(r)) => l.cmp(r),
+        (Field::Byte(l), Field::Byte(r)) => l.cmp(r),
+        (Field::Short(l), Field::Short(r)) => l.cmp(r),
+        (Field::Int(l), Field::Int(r)) => l.cmp(r),
+        (Field::Long(l), Field::Long(r)) => l.cmp(r),
+        (Field::UByte(l), Field::UByte(r)) => l.cmp(r),
+        (Field::UShort(l), Field::UShort(r)) => l.cmp(r),
+        (Field::UInt(l), Field::UInt(r)) => l.cmp(r),
+        (Field::ULong(l), Field::ULong(r)) => l.cmp(r),
+        (Field::Float(l), Field::Float(r)) => l.total_cmp(r),
+        (Field::Double(l), Field::Double(r)) => l.total_cmp(r),
+        (Field::Str(l), Field::Str(r)) => l.cmp(r),
+        (Field::Bytes(l), Field::Bytes(r)) => l.data().cmp(r.data()),
+        (Field::Date(l), Field::Date(r)) => l.cmp(r),
+        (Field::TimestampMillis(l), Field::TimestampMillis(r)) => l.cmp(r),
+        (Field::TimestampMicros(l), Field::TimestampMicros(r)) => l.cmp(r),
+        (l, r) => panic!("Fields {l:?} and {r:?} can not be compared"),
+    }
+}
+
+/// Compare two rows on a composite key, consisting of the columns 'cols' in order of precedence.
+pub fn compare_columns(left: &Row, right: &Row, cols: &[usize]) -> Ordering {
+    cols.iter()
+        .map(|col| compare_fields(get_field(left, *col), get_field(right, *col)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_columns, compare_fields, get_field};
+    use crate::rowwritebuffer::create_row;
+    use parquet::record::Field;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_compare_fields() {
+        let row = create_row(vec![
+            ("id".to_owned(), Field::Long(3)),
+            ("account".to_owned(), Field::Str("abc".to_owned())),
+        ]);
+        assert_eq!(get_field(&row, 1), &Field::Str("abc".to_owned()));
+
+        assert_eq!(compare_fields(&Field::Long(1), get_field(&row, 0)), Ordering::Less);
+        assert_eq!(compare_fields(get_field(&row, 1), &Field::Str("abb".to_owned())), Ordering::Greater);
+        assert_eq!(compare_fields(&Field::Null, &Field::Int(0)), Ordering::Less);
+        assert_eq!(compare_fields(&Field::Double(1.5), &Field::Double(1.5)), Ordering::Equal);
+
+        let other = create_row(vec![
+            ("id".to_owned(), Field::Long(3)),
+            ("account".to_owned(), Field::Str("abd".to_owned())),
+        ]);
+        assert_eq!(compare_columns(&row, &other, &[0]), Ordering::Equal);
+        assert_eq!(compare_columns(&row, &other, &[0, 1]), Ordering::Less);
+        assert_eq!(compare_columns(&other, &row, &[1, 0]), Ordering::Greater);
+    }
+}
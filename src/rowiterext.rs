@@ -1,40 +1,227 @@
-use crate::parquet_reader::{get_parquet_reader, ParquetReaderEnum};
+use crate::parquet_reader::{
+    get_parquet_reader, get_parquet_reader_filtered, ParquetReaderEnum, RowGroupPredicate,
+};
+use crate::predicate::{Predicate, RowFilter};
+use crate::progress::Progress;
+use crate::REPORT_ROWS_STEP;
 use parquet::{
     record::{reader::RowIter, Row},
     schema::{parser::parse_message_type, types::Type},
 };
-use std::mem;
+use std::{
+    mem, panic,
+    sync::mpsc::{self, Receiver},
+    thread::{self, JoinHandle},
+    vec,
+};
+
+/// Maximal number of rows that a prefetching RowIterExt sends at once to the consuming thread.
+const PREFETCH_BATCH: usize = 1_000;
+
+/// A mapping that is applied to each row that is read, for example to add columns.
+pub type RowMapper = Box<dyn Fn(Row) -> Row + Send>;
+
+/// The source of the rows of a RowIterExt.
+enum RowSource<'a> {
+    /// The rows are decoded on the calling thread.
+    Direct(RowIter<'a>),
+    /// The rows are decoded on a background thread and received in batches.
+    Prefetched {
+        receiver: Receiver<Vec<Row>>,
+        batch: vec::IntoIter<Row>,
+        handle: Option<JoinHandle<()>>,
+    },
+}
+
+/// Re-raise the panic of the background thread of a prefetching RowIterExt, such that a failing read is not mistaken
+/// for the end of the input.
+fn propagate_panic(handle: &mut Option<JoinHandle<()>>) {
+    if let Some(handle) = handle.take() {
+        if let Err(payload) = handle.join() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl<'a> RowSource<'a> {
+    fn next(&mut self) -> Option<Row> {
+        match self {
+            RowSource::Direct(row_iter) => row_iter.next(),
+            RowSource::Prefetched {
+                receiver,
+                batch,
+                handle,
+            } => loop {
+                if let Some(row) = batch.next() {
+                    return Some(row);
+                }
+                match receiver.recv() {
+                    Ok(rows) => *batch = rows.into_iter(),
+                    Err(_) => {
+                        // the background thread is ready (or failed)
+                        propagate_panic(handle);
+                        return None;
+                    }
+                }
+            },
+        }
+    }
+}
 
 pub struct RowIterExt<'a> {
-    row_iter: RowIter<'a>,
+    path: &'a str,
+    row_iter: RowSource<'a>,
     schema: Type,
     head: Option<Row>,
+    row_mapper: Option<RowMapper>,
+    row_filter: Option<RowFilter>,
+    progress: Option<Progress>,
+    unreported_rows: u64,
 }
 
 impl<'a> RowIterExt<'a> {
     pub fn new(path: &'a str) -> Self {
-        if let Some((mut row_iter, schema)) = get_parquet_iter(path, None) {
+        Self::new_filtered(path, None)
+    }
+
+    /// Create a RowIterExt that only reads the row-groups accepted by 'predicate'.
+    pub fn new_filtered(path: &'a str, predicate: Option<RowGroupPredicate>) -> Self {
+        Self::new_projected(path, None, predicate)
+    }
+
+    /// Create a RowIterExt that returns rows according to the projection 'message_type', so the order of the columns
+    /// follows the 'message_type' instead of the file. Only the row-groups accepted by 'predicate' are read.
+    pub fn new_projected(path: &'a str, message_type: Option<&'a str>, predicate: Option<RowGroupPredicate>) -> Self {
+        if let Some((row_iter, schema)) = get_parquet_iter_filtered(path, message_type, predicate) {
+            let mut row_iter = RowSource::Direct(row_iter);
             let head = row_iter.next();
             RowIterExt {
+                path,
                 row_iter,
                 schema,
                 head,
+                row_mapper: None,
+                row_filter: None,
+                progress: None,
+                unreported_rows: 0,
             }
         } else {
             panic!("Failed to create iterator for {}", path);
         }
     }
 
+    /// Create a RowIterExt that only returns the rows (projected on 'message_type') that satisfy 'predicate'. The row-groups
+    /// that can not contain such rows according to their statistics are skipped. The columns of the predicate should be
+    /// part of the projection.
+    pub fn new_where(path: &'a str, message_type: Option<&'a str>, predicate: &Predicate) -> Self {
+        let mut row_iter_ext = Self::new_projected(path, message_type, Some(predicate.row_group_predicate()));
+        let row_filter = predicate.bind(&row_iter_ext.schema);
+        let head_matches = row_iter_ext.head.as_ref().map_or(true, |row| row_filter.matches(row));
+        row_iter_ext.row_filter = Some(row_filter);
+        if !head_matches {
+            row_iter_ext.head = row_iter_ext.next_row();
+        }
+        row_iter_ext
+    }
+
+    /// Create a RowIterExt that decodes the rows (projected on 'message_type') on a background thread, which reads ahead
+    /// up to about 'read_ahead' rows. This hides the latency of fetching the next row-group, for example of an S3-object.
+    pub fn new_prefetched(path: &'a str, message_type: Option<&str>, read_ahead: usize) -> Self {
+        let batch_size = read_ahead.clamp(1, PREFETCH_BATCH);
+        let (schema_sink, schema_receiver) = mpsc::sync_channel(1);
+        let (row_sink, receiver) = mpsc::sync_channel((read_ahead / batch_size).max(1));
+
+        let thread_path = path.to_owned();
+        let message_type = message_type.map(|mt| mt.to_owned());
+        let mut handle = Some(thread::spawn(move || {
+            let mut input = RowIterExt::new_projected(&thread_path, message_type.as_deref(), None);
+            if schema_sink.send(input.schema().clone()).is_err() {
+                return;
+            }
+            while let Some(rows) = input.take(batch_size as u64) {
+                if row_sink.send(rows).is_err() {
+                    break; // the consumer stopped reading
+                }
+            }
+        }));
+
+        let schema = match schema_receiver.recv() {
+            Ok(schema) => schema,
+            Err(_) => {
+                propagate_panic(&mut handle);
+                panic!("Failed to create iterator for {}", path);
+            }
+        };
+        let mut row_iter = RowSource::Prefetched {
+            receiver,
+            batch: Vec::new().into_iter(),
+            handle,
+        };
+        let head = row_iter.next();
+        RowIterExt {
+            path,
+            row_iter,
+            schema,
+            head,
+            row_mapper: None,
+            row_filter: None,
+            progress: None,
+            unreported_rows: 0,
+        }
+    }
+
+    /// Apply the 'row_mapper' to all rows, where 'schema' is the schema of the mapped rows.
+    pub fn with_row_mapper(mut self, row_mapper: RowMapper, schema: Type) -> Self {
+        self.head = self.head.take().map(&row_mapper);
+        self.schema = schema;
+        self.row_mapper = Some(row_mapper);
+        self
+    }
+
+    /// Report the rows that are read to 'progress', in batches of 'REPORT_ROWS_STEP' rows.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.unreported_rows = self.head.is_some() as u64;
+        self.progress = Some(progress);
+        self
+    }
+
     pub fn schema(&self) -> &Type {
         &self.schema
     }
 
+    fn report_rows_read(&mut self, exhausted: bool) {
+        if let Some(progress) = &self.progress {
+            if self.unreported_rows >= REPORT_ROWS_STEP || (exhausted && self.unreported_rows > 0) {
+                progress.rows_read(self.path, self.unreported_rows);
+                self.unreported_rows = 0;
+            }
+        }
+    }
+
+    fn next_row(&mut self) -> Option<Row> {
+        let row = loop {
+            let row = self.row_iter.next();
+            if self.progress.is_some() {
+                self.unreported_rows += row.is_some() as u64;
+                self.report_rows_read(row.is_none());
+            }
+            match (&row, &self.row_filter) {
+                (Some(row), Some(row_filter)) if !row_filter.matches(row) => continue,
+                _ => break row,
+            }
+        };
+        match &self.row_mapper {
+            Some(row_mapper) => row.map(row_mapper),
+            None => row,
+        }
+    }
+
     pub fn head(&self) -> &Option<Row> {
         &self.head
     }
 
     pub fn update_head(&mut self) -> (Row, bool) {
-        let mut head = self.row_iter.next();
+        let mut head = self.next_row();
         mem::swap(&mut self.head, &mut head);
         (head.unwrap(), self.head.is_none())
     }
@@ -49,7 +236,7 @@ impl<'a> RowIterExt<'a> {
         data.push(self.head.take().unwrap());
         let mut num_rows = 0;
         while num_rows < max_rows {
-            if let Some(row) = self.row_iter.next() {
+            if let Some(row) = self.next_row() {
                 data.push(row);
                 num_rows += 1;
             } else {
@@ -57,7 +244,7 @@ impl<'a> RowIterExt<'a> {
             }
         }
         // set the new head again, as not all data is consumed.
-        self.head = self.row_iter.next();
+        self.head = self.next_row();
         return Some(data);
     }
 
@@ -85,11 +272,20 @@ fn get_projection<'a>(message_type: Option<&'a str>) -> Option<Type> {
 pub fn get_parquet_iter<'a>(
     path: &'a str,
     message_type: Option<&'a str>,
+) -> Option<(RowIter<'a>, Type)> {
+    get_parquet_iter_filtered(path, message_type, None)
+}
+
+/// create an iterator over the data of a Parquet-file that only visits the row-groups accepted by the 'predicate'.
+pub fn get_parquet_iter_filtered<'a>(
+    path: &'a str,
+    message_type: Option<&'a str>,
+    predicate: Option<RowGroupPredicate>,
 ) -> Option<(RowIter<'a>, Type)> {
     //    let proj = parse_message_type(message_type).ok();
     let proj = get_projection(message_type);
 
-    let reader = get_parquet_reader(path);
+    let reader = get_parquet_reader_filtered(path, predicate);
 
     let schema = if let Some(projection) = proj.as_ref() {
         projection.clone()
@@ -105,7 +301,7 @@ pub fn get_parquet_iter<'a>(
     .project(proj); // make the mapping to the right schema
 
     if row_iter.is_err() {
-        println!("Opening {path} failed with error: {:?}", row_iter.err());
+        eprintln!("Opening {path} failed with error: {:?}", row_iter.err());
         return None;
     }
 
@@ -123,6 +319,29 @@ pub fn read_rows(path: &str, max_rows: Option<usize>, message_type: &str) -> Vec
         .collect()
 }
 
+/// create an iterator over the rows of a Parquet-file that satisfy 'predicate'. The row-groups that can not contain such
+/// rows according to their statistics are not read. The columns of the predicate should be part of the projection.
+pub fn get_parquet_iter_where<'a>(
+    path: &'a str,
+    message_type: Option<&'a str>,
+    predicate: &Predicate,
+) -> Option<(impl Iterator<Item = Row> + 'a, Type)> {
+    let (row_iter, schema) = get_parquet_iter_filtered(path, message_type, Some(predicate.row_group_predicate()))?;
+    let row_filter = predicate.bind(&schema);
+    Some((row_iter.filter(move |row| row_filter.matches(row)), schema))
+}
+
+/// read the rows that satisfy 'predicate' (see 'get_parquet_iter_where') up to a maximum and return these as a vector
+pub fn read_rows_where(path: &str, max_rows: Option<usize>, message_type: &str, predicate: &Predicate) -> Vec<Row> {
+    let max_rows = max_rows.unwrap_or(1_000_000_000);
+
+    get_parquet_iter_where(path, Some(message_type), predicate)
+        .unwrap()
+        .0
+        .take(max_rows)
+        .collect()
+}
+
 /// run over a parquet row_iter and read rows up to a maximum and return these as a vector with step-size applied.
 /// Stepsize should be bigger than 0.
 pub fn read_rows_stepped(path: &str, step_size: usize, message_type: &str) -> Vec<Row> {
@@ -181,3 +400,30 @@ pub mod ttest {
         data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RowIterExt;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_prefetched_reads_all_rows() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = scratch.path("prefetched.parquet");
+        let path = path.as_str();
+        let schema = Arc::new(parse_message_type("message schema { REQUIRED INT64 id; }").unwrap());
+        let mut row_writer = RowWriteBuffer::new(path, schema, 1000).unwrap();
+        (0..2500).for_each(|id| row_writer.append_row(create_row(vec![("id".to_owned(), Field::Long(id))])));
+        row_writer.close();
+
+        let mut ids = Vec::new();
+        RowIterExt::new_prefetched(path, None, 10).drain(&mut |row: Row| ids.push(row.get_long(0).unwrap()));
+        assert_eq!(ids, (0..2500).collect::<Vec<i64>>());
+    }
+}
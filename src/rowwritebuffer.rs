@@ -1,6 +1,8 @@
+use crate::progress::Progress;
 use async_bridge;
 use parquet::{
     errors::{ParquetError, Result},
+    file::metadata::KeyValue,
     record::{Field, Row},
     schema::types::Type,
 };
@@ -20,34 +22,60 @@ mod rowwriter;
 const CHANNEL_SIZE: usize = 2;
 
 pub struct RowWriteBuffer {
+    path: String,
     max_row_group: usize,
     buffer: Vec<Row>,
     write_sink: Option<SyncSender<Vec<Row>>>,
     writer_handle: Option<tokio::task::JoinHandle<()>>, // thread::JoinHandle<()>
+    progress: Progress,
 }
 
 impl RowWriteBuffer {
     pub fn new(path: &str, schema: Arc<Type>, group_size: usize) -> Result<RowWriteBuffer> {
+        Self::new_with_metadata(path, schema, group_size, None)
+    }
+
+    /// Create a RowWriteBuffer that stores the 'key_value_metadata' in the footer of the file.
+    pub fn new_with_metadata(
+        path: &str,
+        schema: Arc<Type>,
+        group_size: usize,
+        key_value_metadata: Option<Vec<KeyValue>>,
+    ) -> Result<RowWriteBuffer> {
+        Self::new_with_progress(path, schema, group_size, key_value_metadata, Progress::default())
+    }
+
+    /// Create a RowWriteBuffer that reports the rows written and the row-groups flushed to 'progress'.
+    pub fn new_with_progress(
+        path: &str,
+        schema: Arc<Type>,
+        group_size: usize,
+        key_value_metadata: Option<Vec<KeyValue>>,
+        progress: Progress,
+    ) -> Result<RowWriteBuffer> {
         let (write_sink, rec_buffer) = mpsc::sync_channel(CHANNEL_SIZE);
 
         let path_clone = path.to_owned();
+        let writer_progress = progress.clone();
 
         let writer_handle = async_bridge::spawn_async(async move {
             //} || {
 
             // here a channel-writer is started and will run until the rec_buffer is closed by all senders (typically one sender)
             // each vector of rows received over the channel via the rec_buffer is writen as a separate row_group.
-            match rowwriter::RowWriter::channel_writer(rec_buffer, &path_clone, schema) {
+            match rowwriter::RowWriter::channel_writer(rec_buffer, &path_clone, schema, key_value_metadata, writer_progress) {
                 Ok(()) => (),
-                Err(err) => println!("Writing file '{path_clone:?}'failed with errors {:?}", err),
+                Err(err) => eprintln!("Writing file '{path_clone:?}'failed with errors {:?}", err),
             }
         });
 
         let row_writer = RowWriteBuffer {
+            path: path.to_owned(),
             max_row_group: group_size,
             buffer: Vec::with_capacity(group_size),
             write_sink: Some(write_sink),
             writer_handle: Some(writer_handle),
+            progress,
         };
 
         Ok(row_writer)
@@ -59,6 +87,7 @@ impl RowWriteBuffer {
 
     pub fn flush(&mut self) -> Result<()> {
         let rows_to_write = mem::take(&mut self.buffer);
+        let num_rows = rows_to_write.len() as u64;
 
         match self
             .write_sink
@@ -66,15 +95,14 @@ impl RowWriteBuffer {
             .expect("Write_sink should still exist (but None)")
             .send(rows_to_write)
         {
-            Ok(()) => Ok(()),
-            Err(err) => {
-                println!("ERROR during flush (sending to write_sink):");
-                println!("   Source: {:?}", err.source());
-
-                Err(ParquetError::General(format!(
-                    "Error during flush: {err:#?}"
-                )))
+            Ok(()) => {
+                self.progress.rows_written(&self.path, num_rows);
+                Ok(())
             }
+            Err(err) => Err(ParquetError::General(format!(
+                "Error during flush: {err:#?} (source: {:?})",
+                err.source()
+            ))),
         }
     }
 
@@ -87,10 +115,9 @@ impl RowWriteBuffer {
         }
     }
 
-    // write a complete row_group to the write-sink. Assumes the current buffer is empty (no pushed rows)
+    // write a complete row_group to the write-sink (an empty row-group is ignored). Assumes the current buffer is empty (no pushed rows)
     pub fn append_row_group(&mut self, rowgroup_data: Vec<Row>) {
         if rowgroup_data.len() == 0 {
-            println!("Received an empty row-group. Ignoring it");
             return;
         }
         let old_buffer = mem::replace(&mut self.buffer, rowgroup_data);
@@ -119,7 +146,6 @@ impl RowWriteBuffer {
             }
         }
 
-        println!("Closing the sending end of the channel.");
         // closing channel will close the writer
         drop(
             self.write_sink
@@ -139,19 +165,6 @@ impl RowWriteBuffer {
     }
 }
 
-impl Drop for RowWriteBuffer {
-    fn drop(&mut self) {
-        match &self.write_sink {
-            Some(_ws) => println!("Write-sink exists"),
-            None => println!("No write-sink (None)"),
-        };
-        match &self.writer_handle {
-            Some(_wh) => println!("Writer_handle exists"),
-            None => println!("No writer_handle (None)"),
-        };
-    }
-}
-
 /// Create a writer based on a string that implements the std::io::Write interface.
 /// If string is prefixed by 'mem:' this will be an in memory buffer, if is is prefixed by 's3:' it will be a s3-object. Otherswise it will be a path on the local file system.
 fn create_writer(path: &str) -> Box<dyn Write> {
@@ -187,8 +200,10 @@ pub fn create_row(fields: Vec<(String, Field)>) -> Row {
 #[cfg(test)]
 pub mod tests {
 
+    use crate::progress::{Progress, ProgressObserver};
     use crate::rowiterext;
     use crate::rowwritebuffer;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
     use parquet::{
         // basic::Compression,
         // data_type::{Int32Type, Int64Type, ByteArrayType, ByteArray},
@@ -205,7 +220,13 @@ pub mod tests {
         schema::parser::parse_message_type,
         //    types::Type}
     };
-    use std::sync::Arc;
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering as AtomicOrdering},
+            Arc,
+        },
+        time::Duration,
+    };
 
     // this is not the right test as I switch to example code
     #[test]
@@ -256,4 +277,43 @@ pub mod tests {
             .collect();
         assert_eq!(input_tuples, output_tuples)
     }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        rows_written: AtomicU64,
+        row_groups: AtomicU64,
+    }
+
+    impl ProgressObserver for CountingObserver {
+        fn rows_written(&self, _path: &str, num_rows: u64) {
+            self.rows_written.fetch_add(num_rows, AtomicOrdering::SeqCst);
+        }
+
+        fn row_group_flushed(&self, _path: &str, _row_group: usize, _num_rows: u64, _duration: Duration) {
+            self.row_groups.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_progress_observer() {
+        let schema = Arc::new(parse_message_type("message schema { REQUIRED INT64 id; }").unwrap());
+        let observer = Arc::new(CountingObserver::default());
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = scratch.path("progress_observer.parquet");
+        let path = path.as_str();
+
+        let mut row_writer = rowwritebuffer::RowWriteBuffer::new_with_progress(
+            path,
+            schema,
+            10,
+            None,
+            Progress::from(Arc::clone(&observer) as Arc<dyn ProgressObserver>),
+        )
+        .unwrap();
+        (0..25).for_each(|id| row_writer.append_row(rowwritebuffer::create_row(vec![("id".to_owned(), Field::Long(id))])));
+        row_writer.close();
+
+        assert_eq!(observer.rows_written.load(AtomicOrdering::SeqCst), 25);
+        assert_eq!(observer.row_groups.load(AtomicOrdering::SeqCst), 3);
+    }
 }
@@ -1,13 +1,15 @@
 use crate::parquet_writer::{self, ParquetWriter};
+use crate::row_compare::get_field;
+use crate::progress::Progress;
 use parquet::{
-    basic::{ConvertedType, Type as PhysicalType},
-    data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, FloatType, Int32Type, Int64Type},
     errors::Result,
     file::{
-        metadata::RowGroupMetaData,
+        metadata::{KeyValue, RowGroupMetaData},
         writer::{SerializedColumnWriter, SerializedRowGroupWriter},
     },
-    record::{Row, RowAccessor},
+    record::{Field, Row, RowAccessor},
     schema::types::Type,
 };
 use s3_file::S3Writer;
@@ -16,7 +18,7 @@ use std::{
     fs, io,
     slice::Iter,
     sync::{mpsc::Receiver, Arc},
-    time::{Duration, Instant},
+    time::Instant,
 };
 
 // use memory_stats::memory_stats;
@@ -67,21 +69,22 @@ pub struct RowWriter {
 
 impl RowWriter {
     /// create a row-writer and attach to the channel. The row-writer will be closed when the sender closes the channel.
+    /// Each flushed row-group is reported to 'progress'.
     pub fn channel_writer(
         to_write: Receiver<Vec<Row>>,
         path: &str,
         schema: Arc<Type>,
+        key_value_metadata: Option<Vec<KeyValue>>,
+        progress: Progress,
     ) -> Result<()> {
-        let mut row_writer = Self::create_writer(path, schema)?;
-
-        let mut total_duration = Duration::new(0, 0);
-
-        let mut idx = 0;
-        for rows in to_write.iter() {
-            let duration = row_writer.write_row_group(rows)?;
-            total_duration += duration;
-            idx += 1;
-            println!("rowgroup {idx}: Total-write-duration={total_duration:?}");
+        let mut row_writer = Self::create_writer(path, schema, key_value_metadata)?;
+
+        for (idx, rows) in to_write.iter().enumerate() {
+            let num_rows = rows.len() as u64;
+            let timer = Instant::now();
+            let row_group = row_writer.write_row_group(rows)?;
+            progress.row_group_flushed(path, idx, num_rows, timer.elapsed());
+            progress.bytes_transferred(path, row_group.compressed_size() as u64);
         }
 
         match row_writer.parquet_writer {
@@ -89,14 +92,16 @@ impl RowWriter {
             ParquetWriter::S3Writer(writer) => writer.close().unwrap(),
         };
 
-        println!(" Total write duration {total_duration:?}");
-
         Ok(())
     }
 
-    fn create_writer(path: &str, schema: Arc<Type>) -> Result<RowWriter> {
+    fn create_writer(
+        path: &str,
+        schema: Arc<Type>,
+        key_value_metadata: Option<Vec<KeyValue>>,
+    ) -> Result<RowWriter> {
         let schema_clone = Arc::clone(&schema);
-        let parquet_writer = parquet_writer::get_parquet_writer(path, schema_clone);
+        let parquet_writer = parquet_writer::get_parquet_writer(path, schema_clone, key_value_metadata);
 
         let row_writer = RowWriter {
             parquet_writer,
@@ -110,9 +115,7 @@ impl RowWriter {
     //     Ok(())
     // }
 
-    fn write_row_group(&mut self, buffer: Vec<Row>) -> Result<Duration> {
-        let timer = Instant::now();
-
+    fn write_row_group(&mut self, buffer: Vec<Row>) -> Result<Arc<RowGroupMetaData>> {
         let mut row_group_writer = match &mut self.parquet_writer {
             ParquetWriter::FileWriter(ref mut writer) => {
                 RowGroupWriter::File(writer.next_row_group().unwrap())
@@ -124,31 +127,41 @@ impl RowWriter {
 
         for (idx, field) in self.schema.get_fields().iter().enumerate() {
             {
+                let optional = field.get_basic_info().repetition() == Repetition::OPTIONAL;
                 if let Some(mut col_writer) = row_group_writer.next_column() {
                     match field.get_basic_info().converted_type() {
                         // TODO: Add the Decimal type (and a few others)
                         ConvertedType::INT_64 => {
-                            write_i64_column(buffer.iter(), idx, &mut col_writer)?
+                            write_i64_column(buffer.iter(), idx, optional, &mut col_writer)?
                         }
                         ConvertedType::UINT_64 => {
-                            write_u64_column(buffer.iter(), idx, &mut col_writer)?
+                            write_u64_column(buffer.iter(), idx, optional, &mut col_writer)?
                         }
                         ConvertedType::INT_32 => {
-                            write_i32_column(buffer.iter(), idx, &mut col_writer)?
+                            write_i32_column(buffer.iter(), idx, optional, &mut col_writer)?
                         }
                         ConvertedType::UTF8 => {
-                            write_utf8_column(buffer.iter(), idx, &mut col_writer)?
+                            write_utf8_column(buffer.iter(), idx, optional, &mut col_writer)?
                         }
                         ConvertedType::TIMESTAMP_MILLIS => {
-                            write_ts_millis_column(buffer.iter(), idx, &mut col_writer)?
+                            write_ts_millis_column(buffer.iter(), idx, optional, &mut col_writer)?
                         } // write the raw type
                         // some more types need to be implemented
                         ConvertedType::NONE => match field.get_physical_type() {
                             PhysicalType::INT64 => {
-                                write_i64_column(buffer.iter(), idx, &mut col_writer)?
+                                write_i64_column(buffer.iter(), idx, optional, &mut col_writer)?
                             }
                             PhysicalType::INT32 => {
-                                write_i32_column(buffer.iter(), idx, &mut col_writer)?
+                                write_i32_column(buffer.iter(), idx, optional, &mut col_writer)?
+                            }
+                            PhysicalType::BOOLEAN => {
+                                write_bool_column(buffer.iter(), idx, optional, &mut col_writer)?
+                            }
+                            PhysicalType::FLOAT => {
+                                write_f32_column(buffer.iter(), idx, optional, &mut col_writer)?
+                            }
+                            PhysicalType::DOUBLE => {
+                                write_f64_column(buffer.iter(), idx, optional, &mut col_writer)?
                             }
                             _ => {
                                 panic!(
@@ -173,55 +186,69 @@ impl RowWriter {
                 }
             }
         }
-        row_group_writer.close()?;
-
-        let elapsed = timer.elapsed();
-
-        println!("Flushing the row-group takes {:?}", elapsed);
-        Ok(elapsed)
+        row_group_writer.close()
     }
 }
 
 // implementations of the columns-writers are implemented as private functions.
 
+/// Get the values of column 'idx' of the 'rows', together with the definition-levels when the column is 'optional'.
+/// A null only gets a definition-level (of 0), so the 'row_accessor' is only applied to the non-null fields.
+fn column_values<T, R>(rows: Iter<Row>, idx: usize, optional: bool, row_accessor: R) -> (Vec<T>, Option<Vec<i16>>)
+where
+    R: Fn(&Row) -> T,
+{
+    if !optional {
+        return (rows.map(row_accessor).collect(), None);
+    }
+    let mut values = Vec::new();
+    let mut def_levels = Vec::new();
+    rows.for_each(|row| match get_field(row, idx) {
+        Field::Null => def_levels.push(0),
+        _ => {
+            values.push(row_accessor(row));
+            def_levels.push(1);
+        }
+    });
+    (values, Some(def_levels))
+}
+
 fn write_i64_column_aux<R>(
     rows: Iter<Row>,
+    idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
     row_acccessor: R,
 ) -> Result<()>
 where
-    R: FnMut(&Row) -> i64,
+    R: Fn(&Row) -> i64,
 {
-    let column: Vec<i64> = rows.map(row_acccessor).collect();
-    // let the_min = column.iter().min().unwrap();
-    // let the_max = column.iter().max().unwrap();
-
-    // col_writer
-    //     .typed::<Int64Type>()
-    //     .write_batch_with_statistics(&column, None, None, Some(&the_min), Some(&the_max), None)?;
+    let (column, def_levels) = column_values(rows, idx, optional, row_acccessor);
     let the_min = column.iter().min();
     let the_max = column.iter().max();
 
     col_writer
         .typed::<Int64Type>()
-        .write_batch_with_statistics(&column, None, None, the_min, the_max, None)?;
+        .write_batch_with_statistics(&column, def_levels.as_deref(), None, the_min, the_max, None)?;
     Ok(())
 }
 
 fn write_i64_column(
     rows: Iter<Row>,
     idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
 ) -> Result<()> {
-    write_i64_column_aux(rows, col_writer, |row: &Row| row.get_long(idx).unwrap())
+    write_i64_column_aux(rows, idx, optional, col_writer, |row: &Row| row.get_long(idx).unwrap())
 }
 
 fn write_u64_column(
     rows: Iter<Row>,
     idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
 ) -> Result<()> {
-    write_i64_column_aux(rows, col_writer, |row: &Row| {
+    write_i64_column_aux(rows, idx, optional, col_writer, |row: &Row| {
         row.get_ulong(idx).unwrap() as i64
     })
 }
@@ -229,9 +256,10 @@ fn write_u64_column(
 fn write_ts_millis_column(
     rows: Iter<Row>,
     idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
 ) -> Result<()> {
-    write_i64_column_aux(rows, col_writer, |row: &Row| {
+    write_i64_column_aux(rows, idx, optional, col_writer, |row: &Row| {
         row.get_timestamp_millis(idx).unwrap() as i64
     })
 }
@@ -239,47 +267,88 @@ fn write_ts_millis_column(
 fn write_i32_column(
     rows: Iter<Row>,
     idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
 ) -> Result<()> {
-    let column: Vec<i32> = rows.map(|row| row.get_int(idx).unwrap()).collect();
-    let the_min = column.iter().min().unwrap();
-    let the_max = column.iter().max().unwrap();
+    let (column, def_levels) = column_values(rows, idx, optional, |row: &Row| row.get_int(idx).unwrap());
+    let the_min = column.iter().min();
+    let the_max = column.iter().max();
 
     col_writer
         .typed::<Int32Type>()
-        .write_batch_with_statistics(&column, None, None, Some(&the_min), Some(&the_max), None)?;
+        .write_batch_with_statistics(&column, def_levels.as_deref(), None, the_min, the_max, None)?;
     Ok(())
 }
 
 fn write_utf8_column(
     rows: Iter<Row>,
     idx: usize,
+    optional: bool,
     col_writer: &mut SerializedColumnWriter,
 ) -> Result<()> {
-    let column: Vec<ByteArray> = rows
-        .map(|row| row.get_string(idx).unwrap().as_str().into())
-        .collect();
-    let the_min = column
-        .iter()
-        .reduce(|a, b| match a.partial_cmp(b) {
-            Some(Ordering::Equal) => a,
-            Some(Ordering::Greater) => b,
-            Some(Ordering::Less) => a,
-            None => a,
-        })
-        .unwrap();
-    let the_max = column
-        .iter()
-        .reduce(|a, b| match a.partial_cmp(b) {
-            Some(Ordering::Equal) => a,
-            Some(Ordering::Greater) => a,
-            Some(Ordering::Less) => b,
-            None => a,
-        })
-        .unwrap();
+    let (column, def_levels) = column_values(rows, idx, optional, |row: &Row| -> ByteArray {
+        row.get_string(idx).unwrap().as_str().into()
+    });
+    let the_min = column.iter().reduce(|a, b| match a.partial_cmp(b) {
+        Some(Ordering::Equal) => a,
+        Some(Ordering::Greater) => b,
+        Some(Ordering::Less) => a,
+        None => a,
+    });
+    let the_max = column.iter().reduce(|a, b| match a.partial_cmp(b) {
+        Some(Ordering::Equal) => a,
+        Some(Ordering::Greater) => a,
+        Some(Ordering::Less) => b,
+        None => a,
+    });
 
     col_writer
         .typed::<ByteArrayType>()
-        .write_batch_with_statistics(&column, None, None, Some(the_min), Some(the_max), None)?;
+        .write_batch_with_statistics(&column, def_levels.as_deref(), None, the_min, the_max, None)?;
+    Ok(())
+}
+
+fn write_bool_column(
+    rows: Iter<Row>,
+    idx: usize,
+    optional: bool,
+    col_writer: &mut SerializedColumnWriter,
+) -> Result<()> {
+    let (column, def_levels) = column_values(rows, idx, optional, |row: &Row| row.get_bool(idx).unwrap());
+    let the_min = column.iter().min();
+    let the_max = column.iter().max();
+
+    col_writer
+        .typed::<BoolType>()
+        .write_batch_with_statistics(&column, def_levels.as_deref(), None, the_min, the_max, None)?;
+    Ok(())
+}
+
+// floating points are not totally ordered (NaN), so the statistics are left to the column-writer.
+fn write_f32_column(
+    rows: Iter<Row>,
+    idx: usize,
+    optional: bool,
+    col_writer: &mut SerializedColumnWriter,
+) -> Result<()> {
+    let (column, def_levels) = column_values(rows, idx, optional, |row: &Row| row.get_float(idx).unwrap());
+
+    col_writer
+        .typed::<FloatType>()
+        .write_batch(&column, def_levels.as_deref(), None)?;
+    Ok(())
+}
+
+fn write_f64_column(
+    rows: Iter<Row>,
+    idx: usize,
+    optional: bool,
+    col_writer: &mut SerializedColumnWriter,
+) -> Result<()> {
+    let (column, def_levels) = column_values(rows, idx, optional, |row: &Row| row.get_double(idx).unwrap());
+
+    col_writer
+        .typed::<DoubleType>()
+        .write_batch(&column, def_levels.as_deref(), None)?;
     Ok(())
 }
@@ -0,0 +1,144 @@
+use crate::object_remove::remove_object;
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The location where intermediate files/objects are stored.
+#[derive(Clone, Debug)]
+pub enum ScratchLocation {
+    /// A directory on the local file system.
+    Local(PathBuf),
+    /// A prefix within an S3 bucket. The prefix is prepended to the object-names as-is, so include a trailing '/' to get a 'directory'.
+    S3 { bucket: String, prefix: String },
+}
+
+impl Default for ScratchLocation {
+    fn default() -> Self {
+        ScratchLocation::Local(env::temp_dir())
+    }
+}
+
+/// A per-run scratch area for intermediate files/objects.
+/// All paths handed out by the ScratchSpace are removed when it is dropped. A local scratch directory is also removed when
+/// the run fails with a panic, but S3 objects are then left behind (and listed on stderr), as removing these blocks on
+/// network requests while unwinding.
+pub struct ScratchSpace {
+    location: ScratchLocation,
+    run_dir: String,
+    paths: Vec<String>,
+    keep: bool,
+}
+
+impl ScratchSpace {
+    /// Create a scratch area with a name that is unique for this run, based on the 'label', the process-id, the time and a counter.
+    pub fn new(location: &ScratchLocation, label: &str) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let run_id = format!(
+            "parquet_ops-{label}-{}-{nanos}-{}",
+            process::id(),
+            RUN_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let run_dir = match location {
+            ScratchLocation::Local(dir) => {
+                let run_dir = dir.join(&run_id);
+                fs::create_dir_all(&run_dir)
+                    .expect(&format!("Failed to create scratch directory {run_dir:?}"));
+                run_dir.to_str().expect("Scratch directory should be valid UTF-8").to_owned()
+            }
+            ScratchLocation::S3 { bucket, prefix } => format!("s3:{bucket}:{prefix}{run_id}"),
+        };
+
+        ScratchSpace {
+            location: location.clone(),
+            run_dir,
+            paths: Vec::new(),
+            keep: false,
+        }
+    }
+
+    /// Reopen the scratch area 'run_dir' of an earlier (interrupted) run, such that its files can be reused.
+    pub fn resume(location: &ScratchLocation, run_dir: &str) -> Self {
+        if let ScratchLocation::Local(_) = location {
+            fs::create_dir_all(run_dir).expect(&format!("Failed to create scratch directory {run_dir}"));
+        }
+        ScratchSpace {
+            location: location.clone(),
+            run_dir: run_dir.to_owned(),
+            paths: Vec::new(),
+            keep: false,
+        }
+    }
+
+    pub fn run_dir(&self) -> &str {
+        &self.run_dir
+    }
+
+    /// When 'keep' is set the scratch area survives the drop, such that a next run can resume from it.
+    pub fn set_keep(&mut self, keep: bool) {
+        self.keep = keep;
+    }
+
+    /// Get the path for a new file/object named 'name' within the scratch area.
+    pub fn path(&mut self, name: &str) -> String {
+        let path = format!("{}/{name}", self.run_dir);
+        self.paths.push(path.clone());
+        path
+    }
+}
+
+impl Drop for ScratchSpace {
+    fn drop(&mut self) {
+        if self.keep {
+            // the scratch area is kept to allow a resume.
+            return;
+        }
+        match &self.location {
+            ScratchLocation::Local(_) => {
+                if let Err(err) = fs::remove_dir_all(&self.run_dir) {
+                    eprintln!("Failed to remove scratch directory {}: {err}", self.run_dir);
+                }
+            }
+            ScratchLocation::S3 { .. } if thread::panicking() => self
+                .paths
+                .iter()
+                .for_each(|path| eprintln!("Scratch object {path} is not removed, as the run failed")),
+            ScratchLocation::S3 { .. } => self.paths.iter().for_each(|path| {
+                if let Err(err) = remove_object(path) {
+                    eprintln!("Failed to remove scratch object: {err}");
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScratchLocation, ScratchSpace};
+    use std::{fs, path::Path};
+
+    #[test]
+    fn test_scratch_space_is_unique_and_removed() {
+        let location = ScratchLocation::default();
+        let mut scratch_1 = ScratchSpace::new(&location, "test");
+        let scratch_2 = ScratchSpace::new(&location, "test");
+        assert_ne!(scratch_1.run_dir, scratch_2.run_dir);
+
+        let path = scratch_1.path("intermediate-0-0.parquet");
+        fs::write(&path, b"PAR1").unwrap();
+        let run_dir = scratch_1.run_dir.clone();
+
+        drop(scratch_1);
+        assert!(!Path::new(&path).exists());
+        assert!(!Path::new(&run_dir).exists());
+    }
+}
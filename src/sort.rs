@@ -1,33 +1,298 @@
 use super::rowiterext::RowIterExt;
-use std::sync::Arc;
+use parquet::{
+    record::{Field, Row},
+    schema::types::Type,
+};
+use std::{cmp::Ordering, path::PathBuf, sync::Arc};
 use crate::object_size::get_object_size;
+use crate::parquet_reader::get_parquet_reader;
+use crate::progress::Progress;
+use crate::projection::{projection_message_type, ColumnProjection, OutputColumn};
+use crate::scratch::ScratchLocation;
+mod bucket;
+pub use bucket::bucket_sort;
+mod checkpoint;
+mod dedup;
+pub use dedup::{DedupOptions, KeepRow};
+use dedup::Deduplicator;
 mod parquet_key;
-use parquet_key::{SortMultistageParquet, ParquetKey};
+use parquet_key::{FnSortKey, ParquetKey};
+pub use parquet_key::SortMultistageParquet;
 mod partition;
+mod presorted;
+use presorted::{copy_sorted, detect_sortedness, merge_sorted_row_groups, Sortedness, MAX_MERGED_ROW_GROUPS};
 mod sort_algo;
+mod sorted_output;
+pub(crate) use sorted_output::{rows_per_file, SortedOutput};
+mod top_n;
+pub use top_n::sort_top_n;
 use sort_algo::{sort_multistage, sort_simple};
 
 const MAX_SIZE_SIMPLE_SORT: u64 = 2_000_000_000;
 
+/// Options that tune the sort. The default corresponds to the behaviour of 'sort'.
+#[derive(Clone, Debug)]
+pub struct SortOptions {
+    /// Number of worker threads used by the multistage sort. With 1 worker all stages run sequentially on the calling thread.
+    /// Stage-2 sorts up to 'num_workers' partitions concurrently, each held completely in memory. As the input is split in
+    /// '2 * num_workers' partitions, about half of the (projected) rows are in memory at once, against a third with 1 worker.
+    pub num_workers: usize,
+    /// Guarantee that rows with equal keys retain their original input order, also when running with multiple workers.
+    /// An unstable sort is somewhat faster.
+    pub stable: bool,
+    /// Location for the intermediate files of the multistage sort. These are removed when the sort finishes or fails.
+    pub scratch: ScratchLocation,
+    /// Path of a local manifest that records the progress of the multistage sort. If a sort fails, a rerun with the same
+    /// inputs and options resumes from the last completed step. The intermediate files are kept until the sort completes.
+    pub checkpoint: Option<PathBuf>,
+    /// Only keep a single row per key. When de-duplicating the sort is always stable.
+    pub dedup: Option<DedupOptions>,
+    /// Check whether the input is already sorted (globally or per row-group) before sorting. This requires an additional
+    /// scan of the key-column, but an already sorted input is copied and sorted row-groups are merged instead of sorted.
+    /// Sorted row-groups are only merged for inputs of at most 32 row-groups, as each of them needs its own reader.
+    /// Off by default, such that a plain sort does not pay for the additional scan.
+    pub detect_presorted: bool,
+    /// When set the output is split in multiple files with non-overlapping key-ranges of about 'target_file_bytes' each,
+    /// named '<sorted_path>-<NNNNN>.parquet', together with a manifest '<sorted_path>-manifest.parquet' listing the min/max
+    /// key of each file (the '.parquet' extension of 'sorted_path' is dropped). When 'columns' drops the sort-key the files
+    /// are cut at a fixed number of rows instead, so a run of equal keys can span two files, and no manifest is written.
+    pub target_file_bytes: Option<u64>,
+    /// Receives the phases of the sort, the rows read from the input, and the rows written to the output.
+    pub progress: Progress,
+    /// The columns of the output, in output order and optionally renamed. None writes all columns of the input.
+    /// Only the output columns and the columns needed by the sort-key and the de-duplication are read from the input.
+    /// The output is only marked as sorted if the sort-key is one of the output columns.
+    pub columns: Option<Vec<OutputColumn>>,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            num_workers: 1,
+            stable: true,
+            scratch: ScratchLocation::default(),
+            checkpoint: None,
+            dedup: None,
+            detect_presorted: false,
+            target_file_bytes: None,
+            progress: Progress::default(),
+            columns: None,
+        }
+    }
+}
+
 /// sort the input in one pass and writer it to the sorted-path
 pub fn sort(input_path: &str, sorted_path: &str, sort_field_name: &str) {
+    sort_with_options(input_path, sorted_path, sort_field_name, &SortOptions::default())
+}
+
+/// sort the input and write it to the sorted-path, using the 'options' to select the behaviour of the sort.
+pub fn sort_with_options(input_path: &str, sorted_path: &str, sort_field_name: &str, options: &SortOptions) {
+    assert!(options.num_workers > 0, "SortOptions.num_workers should be at least 1");
+
+    // only read the columns that are written or that are needed by the sort
+    let read_type = options.columns.as_ref().map(|columns| {
+        let mut names: Vec<&str> = columns.iter().map(|column| column.name.as_str()).collect();
+        names.push(sort_field_name);
+        if let Some(dedup_options) = &options.dedup {
+            names.extend(dedup_options.unique_key.as_deref());
+            if let KeepRow::MaxBy(name) = &dedup_options.keep {
+                names.push(name.as_str());
+            }
+        }
+        let file_schema = get_parquet_reader(input_path).metadata().file_metadata().schema().clone();
+        projection_message_type(&file_schema, &names)
+    });
+
     // Open reader 'RowIterExt' such that we get access to the schema (and know the file/object is readable)
-    let input = RowIterExt::new(input_path);
+    let input =
+        RowIterExt::new_projected(input_path, read_type.as_deref(), None).with_progress(options.progress.clone());
+    assert!(input.head().is_some());
+    let schema = Arc::new(input.schema().clone());
+
+    let mut parquet_key = ParquetKey::new(sort_field_name.to_owned(), Arc::clone(&schema));
+
+    let mut options = options.clone();
+    let mut dedup = None;
+    if let Some(dedup_options) = &options.dedup {
+        // the row that is kept of a series of duplicates depends on the input order of equal keys.
+        options.stable = true;
+        if let Some(unique_key) = &dedup_options.unique_key {
+            // sort on (sort-key, unique-key) such that duplicates are adjacent
+            parquet_key = parquet_key.with_secondary(unique_key, Arc::clone(&schema));
+        }
+        dedup = Some(Deduplicator::new(dedup_options, parquet_key.sort_col(), Arc::clone(&schema)));
+    }
+
+    let rows_per_file = options
+        .target_file_bytes
+        .map(|target| rows_per_file(&[input_path], target));
+    let projection = output_projection(&schema, &options);
+    let create_output = |algorithm: &str| {
+        options.progress.phase(sorted_path, algorithm);
+        let sort_metadata = parquet_key.sort_metadata(algorithm);
+        let (output_schema, sort_metadata) = match &projection {
+            Some(projection) => (projection.schema(), projection.sort_metadata(&sort_metadata)),
+            None => (Arc::clone(&schema), Some(sort_metadata)),
+        };
+        SortedOutput::new(sorted_path, output_schema, sort_metadata, rows_per_file, options.progress.clone())
+            .with_projection(projection.clone())
+    };
+
+    // the detection only considers the primary key, so it can not be used when sorting on a secondary column as well.
+    if options.detect_presorted && !parquet_key.has_secondary() {
+        options.progress.phase(sorted_path, "detect-presorted");
+        let sortedness = detect_sortedness(input_path, &parquet_key);
+        options
+            .progress
+            .message(&format!("The input '{input_path}' is {sortedness:?} on key '{sort_field_name}'"));
+        match sortedness {
+            Sortedness::Sorted => {
+                copy_sorted(input_path, read_type.as_deref(), create_output("presorted"), dedup.as_ref());
+                return;
+            }
+            Sortedness::SortedRowGroups
+                if get_parquet_reader(input_path).metadata().num_row_groups() <= MAX_MERGED_ROW_GROUPS =>
+            {
+                let output = create_output("merge-row-groups");
+                merge_sorted_row_groups(input_path, read_type.as_deref(), output, &parquet_key, dedup.as_ref());
+                return;
+            }
+            Sortedness::SortedRowGroups | Sortedness::Unsorted => (),
+        }
+    }
+
+    sort_input(input, input_path, sorted_path, &parquet_key, create_output, &options, dedup.as_ref());
+}
+
+/// sort the input on a user-defined 'sort_key', for example a derived key like 'lower(account)', and write it to the sorted-path.
+/// The output is not marked as sorted, as it is not sorted on a column. For the same reason 'options.dedup' and
+/// 'options.target_file_bytes' are not supported, and presorted inputs are not detected. As the columns used by the 'sort_key'
+/// are not known, all columns are read, also when 'options.columns' selects some of them.
+pub fn sort_with_key(input_path: &str, sorted_path: &str, sort_key: &dyn SortMultistageParquet, options: &SortOptions) {
+    assert!(options.num_workers > 0, "SortOptions.num_workers should be at least 1");
+    assert!(
+        options.dedup.is_none() && options.target_file_bytes.is_none(),
+        "De-duplication and splitting of the output require a sort on a column"
+    );
+
+    let input = RowIterExt::new(input_path).with_progress(options.progress.clone());
     assert!(input.head().is_some());
     let schema = Arc::new(input.schema().clone());
+    let projection = output_projection(&schema, options);
+    let output_schema = projection.as_ref().map_or_else(|| Arc::clone(&schema), |projection| projection.schema());
+    let create_output = |algorithm: &str| {
+        options.progress.phase(sorted_path, algorithm);
+        SortedOutput::new(sorted_path, Arc::clone(&output_schema), None, None, options.progress.clone())
+            .with_projection(projection.clone())
+    };
 
-    let parquet_key = ParquetKey::new(sort_field_name.to_owned(), Arc::clone(&schema));
+    sort_input(input, input_path, sorted_path, sort_key, create_output, options, None);
+}
+
+/// sort the input with a 'comparator' on complete rows and write it to the sorted-path.
+/// The multistage sort partitions the input on the 'partition_key' extracted from the rows. The order of the partition-keys
+/// should be consistent with the 'comparator', so if 'partition_key(a) < partition_key(b)' then 'comparator(a, b)' should be 'Less'.
+/// See 'sort_with_key' for the limitations. As closures can not be told apart, 'options.checkpoint' is not supported;
+/// implement 'SortMultistageParquet' with a distinct name and use 'sort_with_key' to resume a sort on a derived key.
+pub fn sort_by<C, K>(input_path: &str, sorted_path: &str, comparator: C, partition_key: K, options: &SortOptions)
+where
+    C: Fn(&Row, &Row) -> Ordering + Send + Sync + 'static,
+    K: Fn(&Row) -> Field + Send + Sync + 'static,
+{
+    assert!(
+        options.checkpoint.is_none(),
+        "A checkpointed sort requires a named sort-key, see 'sort_with_key'"
+    );
+    let schema = RowIterExt::new(input_path).schema().clone();
+    let sort_key = FnSortKey::new(&schema, comparator, partition_key);
+    sort_with_key(input_path, sorted_path, &sort_key, options)
+}
+
+/// The projection of the sorted rows with 'schema' on 'options.columns'.
+fn output_projection(schema: &Type, options: &SortOptions) -> Option<ColumnProjection> {
+    options
+        .columns
+        .as_ref()
+        .map(|columns| ColumnProjection::new(schema, columns))
+}
 
+/// Select the simple or the multistage sort, based on the size of the input.
+fn sort_input<F>(
+    input: RowIterExt,
+    input_path: &str,
+    sorted_path: &str,
+    sort_key: &dyn SortMultistageParquet,
+    create_output: F,
+    options: &SortOptions,
+    dedup: Option<&Deduplicator>,
+) where
+    F: Fn(&str) -> SortedOutput,
+{
     // TODO: add size computation to determine the right kind of sort-algorithm
     let obj_size = get_object_size(input_path);
     if obj_size < MAX_SIZE_SIMPLE_SORT {
         sort_simple(
             input,
-            schema,
-            sorted_path,
-            parquet_key.get_record_compare_fn(),
+            create_output("simple"),
+            sort_key.get_record_compare_fn(),
+            options.stable,
+            dedup,
         );
     } else {
-        sort_multistage(input, schema, input_path, sorted_path, parquet_key);
+        let output = create_output("multistage");
+        sort_multistage(input, input_path, sorted_path, output, sort_key, options, dedup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_by, SortOptions};
+    use crate::rowiterext::read_rows;
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use crate::sort_metadata::get_sort_metadata;
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    const MESSAGE_TYPE: &str = "message schema { REQUIRED INT64 id; REQUIRED BINARY account (UTF8); }";
+
+    #[test]
+    fn test_sort_by_custom_key() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let (input_path, sorted_path) = (scratch.path("input.parquet"), scratch.path("sorted.parquet"));
+        let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+        let mut row_writer = RowWriteBuffer::new(&input_path, schema, 10).unwrap();
+        (0..30).for_each(|id| {
+            row_writer.append_row(create_row(vec![
+                ("id".to_owned(), Field::Long(id * 7 % 30)),
+                ("account".to_owned(), Field::Str(format!("account-{:02}", id % 4))),
+            ]))
+        });
+        row_writer.close();
+
+        // descending on the account, and ascending on the id for equal accounts.
+        let comparator = |left: &Row, right: &Row| {
+            let account = |row: &Row| row.get_string(1).unwrap().clone();
+            account(right).cmp(&account(left)).then(left.get_long(0).unwrap().cmp(&right.get_long(0).unwrap()))
+        };
+        let partition_key = |row: &Row| Field::Int(-(row.get_string(1).unwrap()[8..].parse::<i32>().unwrap()));
+        sort_by(&input_path, &sorted_path, comparator, partition_key, &SortOptions::default());
+
+        let sorted: Vec<(String, i64)> = read_rows(&sorted_path, None, MESSAGE_TYPE)
+            .iter()
+            .map(|row| (row.get_string(1).unwrap().clone(), row.get_long(0).unwrap()))
+            .collect();
+        let mut expected = sorted.clone();
+        expected.sort_by(|left, right| right.0.cmp(&left.0).then(left.1.cmp(&right.1)));
+        assert_eq!(sorted.len(), 30);
+        assert_eq!(sorted, expected);
+        assert_eq!(sorted[0], ("account-03".to_owned(), 9));
+
+        // the output is not sorted on a column, so it is not marked as sorted.
+        assert_eq!(get_sort_metadata(&sorted_path), None);
     }
 }
@@ -0,0 +1,184 @@
+use super::parquet_key::ParquetKey;
+use super::sort_algo::sort_rows;
+use crate::row_compare::{compare_fields, get_field};
+use crate::rowiterext::RowIterExt;
+use crate::rowwritebuffer::RowWriteBuffer;
+use crate::scratch::{ScratchLocation, ScratchSpace};
+use parquet::record::{Field, Row};
+use std::sync::Arc;
+
+const MAX_BUCKET_BLOCK: u64 = 100_000;
+
+/// Seed of the Murmur3 hash that Spark uses for its hash-partitioning and bucketing.
+const SPARK_HASH_SEED: u32 = 42;
+
+const C1: u32 = 0xcc9e2d51;
+const C2: u32 = 0x1b873593;
+
+fn mix_k1(k1: u32) -> u32 {
+    k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2)
+}
+
+fn mix_h1(h1: u32, k1: u32) -> u32 {
+    (h1 ^ k1).rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64)
+}
+
+fn fmix(mut h1: u32, length: u32) -> u32 {
+    h1 ^= length;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85ebca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2ae35);
+    h1 ^= h1 >> 16;
+    h1
+}
+
+fn hash_int(value: i32, seed: u32) -> u32 {
+    fmix(mix_h1(seed, mix_k1(value as u32)), 4)
+}
+
+fn hash_long(value: i64, seed: u32) -> u32 {
+    let h1 = mix_h1(seed, mix_k1(value as u32));
+    let h1 = mix_h1(h1, mix_k1((value >> 32) as u32));
+    fmix(h1, 8)
+}
+
+/// Spark hashes the trailing bytes one at a time as a (sign-extended) int, which deviates from the reference Murmur3.
+fn hash_bytes(bytes: &[u8], seed: u32) -> u32 {
+    let aligned = bytes.len() - bytes.len() % 4;
+    let h1 = bytes[..aligned]
+        .chunks(4)
+        .fold(seed, |h1, word| mix_h1(h1, mix_k1(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))));
+    let h1 = bytes[aligned..]
+        .iter()
+        .fold(h1, |h1, byte| mix_h1(h1, mix_k1(*byte as i8 as i32 as u32)));
+    fmix(h1, bytes.len() as u32)
+}
+
+/// Compute the hash of 'field' in the same way as the 'hash' function of Spark (Murmur3 with seed 42), such that
+/// the buckets match the buckets of a table that is bucketed by Spark.
+pub fn spark_hash(field: &Field) -> i32 {
+    let seed = SPARK_HASH_SEED;
+    let hash = match field {
+        Field::Null => seed,
+        Field::Bool(b) => hash_int(*b as i32, seed),
+        Field::Byte(v) => hash_int(*v as i32, seed),
+        Field::Short(v) => hash_int(*v as i32, seed),
+        Field::Int(v) => hash_int(*v, seed),
+        Field::Long(v) => hash_long(*v, seed),
+        Field::UByte(v) => hash_int(*v as i32, seed),
+        Field::UShort(v) => hash_int(*v as i32, seed),
+        Field::UInt(v) => hash_long(*v as i64, seed),
+        Field::Date(days) => hash_int(*days as i32, seed),
+        Field::TimestampMillis(millis) => hash_long(*millis as i64 * 1000, seed),
+        Field::TimestampMicros(micros) => hash_long(*micros as i64, seed),
+        Field::Float(v) => {
+            // Spark normalizes -0.0 and NaN before hashing
+            let bits = if *v == 0.0 {
+                0
+            } else if v.is_nan() {
+                0x7fc00000
+            } else {
+                v.to_bits()
+            };
+            hash_int(bits as i32, seed)
+        }
+        Field::Double(v) => {
+            let bits = if *v == 0.0 {
+                0
+            } else if v.is_nan() {
+                0x7ff8000000000000
+            } else {
+                v.to_bits()
+            };
+            hash_long(bits as i64, seed)
+        }
+        Field::Str(s) => hash_bytes(s.as_bytes(), seed),
+        Field::Bytes(b) => hash_bytes(b.data(), seed),
+        other => panic!("Bucketing on field {other:?} is not supported"),
+    };
+    hash as i32
+}
+
+/// The bucket of 'field' when using 'num_buckets' buckets (pmod(hash(field), num_buckets) in Spark).
+pub fn bucket_index(field: &Field, num_buckets: usize) -> usize {
+    spark_hash(field).rem_euclid(num_buckets as i32) as usize
+}
+
+/// Split the input in 'num_buckets' files '<output_prefix>-<NNNNN>.parquet' based on 'hash(key) % num_buckets',
+/// where each bucket is sorted on the 'sort_field_name'. The hash matches the bucketing of Spark.
+/// The input is read in a single pass, while the rows are fanned out to an intermediate file per bucket.
+/// Next each bucket is sorted in memory, so a single bucket should fit in memory.
+/// The intermediate files are stored in 'scratch', for example 'ScratchLocation::default()' for the local temp-directory.
+pub fn bucket_sort(
+    input_path: &str,
+    output_prefix: &str,
+    sort_field_name: &str,
+    num_buckets: usize,
+    scratch: &ScratchLocation,
+) {
+    assert!(num_buckets > 0, "The number of buckets should be at least 1");
+
+    let mut input = RowIterExt::new(input_path);
+    let schema = Arc::new(input.schema().clone());
+    let parquet_key = ParquetKey::new(sort_field_name.to_owned(), Arc::clone(&schema));
+    let col = parquet_key.sort_col();
+
+    // the scratch space (and thus the intermediate files) is removed when it is dropped.
+    let mut scratch = ScratchSpace::new(scratch, "bucket");
+    let bucket_paths: Vec<String> = (0..num_buckets)
+        .map(|bucket| scratch.path(&format!("bucket-{bucket}.parquet")))
+        .collect();
+
+    let mut row_writers: Vec<_> = bucket_paths
+        .iter()
+        .map(|path| RowWriteBuffer::new(path, Arc::clone(&schema), 10000).unwrap())
+        .collect();
+    while let Some(data) = input.take(MAX_BUCKET_BLOCK) {
+        let mut buckets: Vec<Vec<Row>> = (0..num_buckets).map(|_| Vec::new()).collect();
+        data.into_iter().for_each(|row| {
+            let bucket = bucket_index(get_field(&row, col), num_buckets);
+            buckets[bucket].push(row);
+        });
+        buckets
+            .into_iter()
+            .zip(row_writers.iter_mut())
+            .filter(|(data, _)| !data.is_empty())
+            .for_each(|(data, row_writer)| row_writer.append_row_group(data));
+    }
+    row_writers.iter_mut().for_each(|row_writer| row_writer.close());
+
+    bucket_paths.iter().enumerate().for_each(|(bucket, path)| {
+        let bucket_path = format!("{output_prefix}-{bucket:05}.parquet");
+        let mut data = RowIterExt::new(path).take(u64::MAX).unwrap_or_default();
+        // the buckets are not partitioned on the key, so any column-type can be sorted (e.g. a string-key)
+        let comparator = Box::new(move |left: &Row, right: &Row| compare_fields(get_field(left, col), get_field(right, col)));
+        sort_rows(&mut data, comparator, true);
+
+        let key_value_metadata = parquet_key.sort_metadata("bucket").to_key_value_metadata();
+        let mut row_writer =
+            RowWriteBuffer::new_with_metadata(&bucket_path, Arc::clone(&schema), 10000, Some(key_value_metadata)).unwrap();
+        if !data.is_empty() {
+            row_writer.append_row_group(data);
+        }
+        row_writer.close();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_index, spark_hash};
+    use parquet::record::Field;
+
+    #[test]
+    fn test_spark_hash() {
+        // reference values computed with Spark: SELECT hash(1), hash(CAST(1 AS BIGINT)), hash('Spark'), hash(NULL)
+        assert_eq!(spark_hash(&Field::Int(1)), -559580957);
+        assert_eq!(spark_hash(&Field::Long(1)), -1712319331);
+        assert_eq!(spark_hash(&Field::Str("Spark".to_owned())), 228093765);
+        assert_eq!(spark_hash(&Field::Null), 42);
+
+        // pmod keeps the bucket non-negative for negative hashes
+        assert_eq!(bucket_index(&Field::Int(1), 10), 3);
+    }
+}
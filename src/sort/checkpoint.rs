@@ -0,0 +1,170 @@
+use itertools::Itertools;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The progress of a multistage sort, persisted as a small manifest such that a rerun with the same inputs can resume.
+/// The manifest is a text-file with one 'key=value' line per item:
+///  * fingerprint: identifies the run (input, input-size, output, sort-key, number of workers, stability and read schema)
+///  * scratch_dir: the scratch area holding the intermediate files
+///  * boundaries: the partition boundaries of the sort-key
+///  * stage_1_done: the workers that completed stage-1
+///  * stage_2_done: the partitions that are sorted in stage-2
+/// Without a path all operations are no-ops, so the sort does not need to distinguish between the checkpointed and normal mode.
+pub struct SortCheckpoint {
+    path: Option<PathBuf>,
+    fingerprint: String,
+    scratch_dir: Option<String>,
+    boundaries: Option<Vec<String>>,
+    stage_1_done: Vec<usize>,
+    stage_2_done: Vec<usize>,
+}
+
+fn parse_indices(value: &str) -> Vec<usize> {
+    value
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().expect("Checkpoint contains an invalid index"))
+        .collect()
+}
+
+impl SortCheckpoint {
+    /// Create the checkpoint for a run. If a manifest exists at 'path' with the same fingerprint, the progress is loaded from it.
+    /// A manifest of a different run is ignored (and overwritten on the next save).
+    pub fn new(path: Option<&Path>, fingerprint: String) -> Self {
+        let mut checkpoint = SortCheckpoint {
+            path: path.map(|p| p.to_path_buf()),
+            fingerprint,
+            scratch_dir: None,
+            boundaries: None,
+            stage_1_done: Vec::new(),
+            stage_2_done: Vec::new(),
+        };
+
+        if let Some(path) = path {
+            if let Ok(content) = fs::read_to_string(path) {
+                checkpoint.load(&content);
+            }
+        }
+        checkpoint
+    }
+
+    fn load(&mut self, content: &str) {
+        let items: Vec<(&str, &str)> = content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+        if !items.contains(&("fingerprint", self.fingerprint.as_str())) {
+            // the checkpoint belongs to another run, so start from scratch.
+            return;
+        }
+        items.into_iter().for_each(|(key, value)| match key {
+            "scratch_dir" => self.scratch_dir = Some(value.to_owned()),
+            "boundaries" => self.boundaries = Some(value.split(',').map(|s| s.to_owned()).collect()),
+            "stage_1_done" => self.stage_1_done = parse_indices(value),
+            "stage_2_done" => self.stage_2_done = parse_indices(value),
+            _ => (),
+        });
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Write the manifest. The manifest is written to a temporary file first, such that a crash never leaves a partial manifest.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut content = format!("fingerprint={}\n", self.fingerprint);
+        if let Some(scratch_dir) = &self.scratch_dir {
+            content.push_str(&format!("scratch_dir={scratch_dir}\n"));
+        }
+        if let Some(boundaries) = &self.boundaries {
+            content.push_str(&format!("boundaries={}\n", boundaries.join(",")));
+        }
+        content.push_str(&format!("stage_1_done={}\n", self.stage_1_done.iter().join(",")));
+        content.push_str(&format!("stage_2_done={}\n", self.stage_2_done.iter().join(",")));
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content).expect(&format!("Failed to write checkpoint {tmp_path:?}"));
+        fs::rename(&tmp_path, path).expect(&format!("Failed to write checkpoint {path:?}"));
+    }
+
+    /// Remove the manifest, as the run is completed.
+    pub fn remove(&self) {
+        if let Some(path) = &self.path {
+            if let Err(err) = fs::remove_file(path) {
+                eprintln!("Failed to remove checkpoint {path:?}: {err}");
+            }
+        }
+    }
+
+    pub fn scratch_dir(&self) -> Option<&str> {
+        self.scratch_dir.as_deref()
+    }
+
+    pub fn set_scratch_dir(&mut self, scratch_dir: &str) {
+        self.scratch_dir = Some(scratch_dir.to_owned());
+    }
+
+    pub fn boundaries(&self) -> Option<&Vec<String>> {
+        self.boundaries.as_ref()
+    }
+
+    pub fn set_boundaries(&mut self, boundaries: Vec<String>) {
+        self.boundaries = Some(boundaries);
+    }
+
+    pub fn is_stage_1_done(&self, worker: usize) -> bool {
+        self.stage_1_done.contains(&worker)
+    }
+
+    pub fn mark_stage_1_done(&mut self, worker: usize) {
+        self.stage_1_done.push(worker);
+    }
+
+    pub fn is_partition_sorted(&self, partition: usize) -> bool {
+        self.stage_2_done.contains(&partition)
+    }
+
+    pub fn mark_partition_sorted(&mut self, partition: usize) {
+        self.stage_2_done.push(partition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortCheckpoint;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let path = PathBuf::from(scratch.path("checkpoint.manifest"));
+
+        let mut checkpoint = SortCheckpoint::new(Some(&path), "run-1".to_owned());
+        checkpoint.set_scratch_dir("/tmp/scratch");
+        checkpoint.set_boundaries(vec!["10".to_owned(), "20".to_owned()]);
+        checkpoint.mark_stage_1_done(1);
+        checkpoint.mark_partition_sorted(2);
+        checkpoint.save();
+
+        let resumed = SortCheckpoint::new(Some(&path), "run-1".to_owned());
+        assert_eq!(resumed.scratch_dir(), Some("/tmp/scratch"));
+        assert_eq!(resumed.boundaries(), Some(&vec!["10".to_owned(), "20".to_owned()]));
+        assert!(resumed.is_stage_1_done(1));
+        assert!(!resumed.is_stage_1_done(0));
+        assert!(resumed.is_partition_sorted(2));
+
+        // a checkpoint of another run is ignored
+        let other = SortCheckpoint::new(Some(&path), "run-2".to_owned());
+        assert_eq!(other.scratch_dir(), None);
+        assert!(!other.is_stage_1_done(1));
+
+        resumed.remove();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    find_column,
+    row_compare::{compare_fields, get_field},
+};
+use itertools::Itertools;
+use parquet::{record::Row, schema::types::Type};
+use std::{cmp::Ordering, sync::Arc};
+
+/// Which row to keep of a series of rows with the same key.
+#[derive(Clone, Debug)]
+pub enum KeepRow {
+    /// Keep the first row (in input order).
+    First,
+    /// Keep the last row (in input order).
+    Last,
+    /// Keep the row with the largest value in the named column. On ties the first row (in input order) is kept.
+    MaxBy(String),
+}
+
+/// De-duplication of the sorted output.
+#[derive(Clone, Debug)]
+pub struct DedupOptions {
+    /// The column that identifies duplicates. If None the sort-key is used.
+    /// A separate unique key should determine the sort-key (for example a transaction-id when sorting on account),
+    /// as rows are sorted on (sort-key, unique-key) and only adjacent rows are compared.
+    pub unique_key: Option<String>,
+    pub keep: KeepRow,
+}
+
+enum Keep {
+    First,
+    Last,
+    MaxBy(usize),
+}
+
+/// Removes the duplicates from a sorted stream of rows, based on the columns resolved from the 'DedupOptions'.
+pub struct Deduplicator {
+    key_cols: Vec<usize>,
+    keep: Keep,
+}
+
+impl Deduplicator {
+    pub fn new(options: &DedupOptions, sort_col: usize, schema: Arc<Type>) -> Self {
+        let mut key_cols = vec![sort_col];
+        if let Some(unique_key) = &options.unique_key {
+            let (col, _) = find_column(Arc::clone(&schema), unique_key);
+            if col != sort_col {
+                key_cols.push(col);
+            }
+        }
+        let keep = match &options.keep {
+            KeepRow::First => Keep::First,
+            KeepRow::Last => Keep::Last,
+            KeepRow::MaxBy(name) => Keep::MaxBy(find_column(schema, name).0),
+        };
+        Self { key_cols, keep }
+    }
+
+    fn same_key(&self, left: &Row, right: &Row) -> bool {
+        self.key_cols
+            .iter()
+            .all(|col| compare_fields(get_field(left, *col), get_field(right, *col)) == Ordering::Equal)
+    }
+
+    fn select(&self, prev: Row, next: Row) -> Row {
+        match self.keep {
+            Keep::First => prev,
+            Keep::Last => next,
+            Keep::MaxBy(col) => {
+                if compare_fields(get_field(&next, col), get_field(&prev, col)) == Ordering::Greater {
+                    next
+                } else {
+                    prev
+                }
+            }
+        }
+    }
+
+    /// Streaming de-duplication of sorted rows.
+    pub fn dedup<'a, I>(&'a self, rows: I) -> impl Iterator<Item = Row> + 'a
+    where
+        I: Iterator<Item = Row> + 'a,
+    {
+        rows.coalesce(move |prev, next| {
+            if self.same_key(&prev, &next) {
+                Ok(self.select(prev, next))
+            } else {
+                Err((prev, next))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupOptions, Deduplicator, KeepRow};
+    use crate::rowwritebuffer::create_row;
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    fn rows() -> Vec<Row> {
+        vec![(1, 10), (1, 30), (1, 20), (2, 5)]
+            .into_iter()
+            .map(|(id, amount)| {
+                create_row(vec![
+                    ("id".to_owned(), Field::Long(id)),
+                    ("amount".to_owned(), Field::Int(amount)),
+                ])
+            })
+            .collect()
+    }
+
+    fn dedup_amounts(keep: KeepRow) -> Vec<i32> {
+        let schema = Arc::new(
+            parse_message_type("message schema { REQUIRED INT64 id; REQUIRED INT32 amount; }").unwrap(),
+        );
+        let options = DedupOptions { unique_key: None, keep };
+        let dedup = Deduplicator::new(&options, 0, schema);
+        dedup
+            .dedup(rows().into_iter())
+            .map(|row| row.get_int(1).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup() {
+        assert_eq!(dedup_amounts(KeepRow::First), vec![10, 5]);
+        assert_eq!(dedup_amounts(KeepRow::Last), vec![20, 5]);
+        assert_eq!(dedup_amounts(KeepRow::MaxBy("amount".to_owned())), vec![30, 5]);
+    }
+}
@@ -1,35 +1,89 @@
 use parquet::{
     basic::Type as PhysType,
-    record::{Row, RowAccessor},
+    record::{Field, Row, RowAccessor},
     schema::types::Type,
 };
 use std::{cmp::Ordering, sync::Arc};
-use crate::find_field;
+use crate::find_column;
+use crate::metadata::get_message_type;
+use crate::row_compare::{compare_fields, get_field};
+use crate::rowwritebuffer::create_row;
+use crate::sort_metadata::SortMetadata;
 
-pub trait SortMultistageParquet {
+/// The sort-key as used by the simple and the multistage sort. Implement this trait to sort on a derived key (see 'sort_with_key').
+/// The multistage sort samples the input with the projection 'get_partition_message_schema' to obtain the partition-rows.
+/// The partition-rows are ordered with 'get_partition_compare_fn', and a row belongs to the partition of the first
+/// partition-row for which 'get_partition_filter_fn' returns true. This order should be consistent with the record-comparison.
+pub trait SortMultistageParquet: Sync {
     fn get_partition_compare_fn(&self) -> Box<dyn Fn(&Row, &Row) -> Ordering>;
     fn get_record_compare_fn(&self) -> Box<dyn Fn(&Row, &Row) -> Ordering>;
     fn get_partition_filter_fn(&self, partition_row: &Row) -> Box<dyn Fn(&Row) -> bool>;
     fn get_partition_message_schema(&self) -> String;
+
+    /// Label of the key, used in the progress-messages and to identify a checkpointed sort. Keys that order the rows
+    /// differently should have different labels, as a resumed sort reuses the partitions sorted under the same label.
+    fn name(&self) -> &str;
+
+    /// Format a partition-row as a string, such that the partitioning can be stored in a checkpoint.
+    /// When None is returned the partitioning is recomputed when a sort resumes (the sample is deterministic).
+    fn format_partition_value(&self, _partition_row: &Row) -> Option<String> {
+        None
+    }
+
+    /// Parse a value produced by 'format_partition_value' back into a partition-row.
+    fn parse_partition_value(&self, _value: &str) -> Row {
+        panic!("The key '{}' does not support parsing of partition values", self.name())
+    }
 }
 
 pub struct ParquetKey {
     name: String,
     sort_col: usize,
     phys_type: PhysType,
+    secondary_col: Option<usize>,
 }
 
 impl ParquetKey {
     pub fn new(name: String, schema: Arc<Type>) -> Self {
-        let (sort_col, tpe) = find_field(schema, &name);
+        let (sort_col, tpe) = find_column(schema, &name);
         let phys_type = tpe.get_physical_type();
 
         Self {
             name,
             sort_col,
             phys_type,
+            secondary_col: None,
+        }
+    }
+
+    /// Extend the record-comparison with a secondary column, used to order rows with equal keys.
+    /// The partitioning is still based on the primary key only.
+    pub fn with_secondary(mut self, name: &str, schema: Arc<Type>) -> Self {
+        let (col, _) = find_column(schema, name);
+        if col != self.sort_col {
+            self.secondary_col = Some(col);
+        }
+        self
+    }
+
+    pub fn sort_col(&self) -> usize {
+        self.sort_col
+    }
+
+    pub fn has_secondary(&self) -> bool {
+        self.secondary_col.is_some()
+    }
+
+    /// The sort-metadata for a file that is sorted on this key by 'algorithm'.
+    pub fn sort_metadata(&self, algorithm: &str) -> SortMetadata {
+        SortMetadata {
+            key: self.name.clone(),
+            column: self.sort_col,
+            descending: false,
+            algorithm: algorithm.to_owned(),
         }
     }
+
 }
 
 impl SortMultistageParquet for ParquetKey {
@@ -47,7 +101,7 @@ impl SortMultistageParquet for ParquetKey {
 
     fn get_record_compare_fn(&self) -> Box<dyn Fn(&Row, &Row) -> Ordering> {
         let col = self.sort_col;
-        match self.phys_type {
+        let primary: Box<dyn Fn(&Row, &Row) -> Ordering> = match self.phys_type {
             PhysType::INT64 => Box::new(move |left: &Row, right: &Row| {
                 left.get_long(col)
                     .unwrap()
@@ -57,19 +111,29 @@ impl SortMultistageParquet for ParquetKey {
                 left.get_int(col).unwrap().cmp(&right.get_int(col).unwrap())
             }),
             other => panic!("columns of type '{other}' are not supported (yet)!"),
+        };
+
+        match self.secondary_col {
+            None => primary,
+            Some(secondary) => Box::new(move |left: &Row, right: &Row| {
+                primary(left, right).then_with(|| {
+                    compare_fields(get_field(left, secondary), get_field(right, secondary))
+                })
+            }),
         }
     }
 
     fn get_partition_filter_fn(&self, partition_row: &Row) -> Box<dyn Fn(&Row) -> bool> {
+        // the partition-row only contains the key-column
         match self.phys_type {
             PhysType::INT64 => {
                 let col = self.sort_col;
-                let upper_bound = partition_row.get_long(col).unwrap();
+                let upper_bound = partition_row.get_long(0).unwrap();
                 Box::new(move |row: &Row| row.get_long(col).unwrap() <= upper_bound)
             }
             PhysType::INT32 => {
                 let col = self.sort_col;
-                let upper_bound = partition_row.get_int(col).unwrap();
+                let upper_bound = partition_row.get_int(0).unwrap();
                 Box::new(move |row: &Row| row.get_int(col).unwrap() <= upper_bound)
             }
             other => panic!("columns of type '{other}' are not supported (yet)!"),
@@ -91,4 +155,80 @@ impl SortMultistageParquet for ParquetKey {
             self.name
         )
     }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn format_partition_value(&self, partition_row: &Row) -> Option<String> {
+        let value = match self.phys_type {
+            PhysType::INT64 => partition_row.get_long(0).unwrap().to_string(),
+            PhysType::INT32 => partition_row.get_int(0).unwrap().to_string(),
+            other => panic!("columns of type '{other}' are not supported (yet)!"),
+        };
+        Some(value)
+    }
+
+    fn parse_partition_value(&self, value: &str) -> Row {
+        let field = match self.phys_type {
+            PhysType::INT64 => Field::Long(value.parse().expect("Invalid INT64 partition value")),
+            PhysType::INT32 => Field::Int(value.parse().expect("Invalid INT32 partition value")),
+            other => panic!("columns of type '{other}' are not supported (yet)!"),
+        };
+        create_row(vec![(self.name.clone(), field)])
+    }
+}
+
+/// A sort-key defined by closures, see 'sort_by'. The partitioning samples complete rows, such that both closures
+/// can be applied to all rows.
+pub struct FnSortKey<C, K> {
+    message_schema: String,
+    comparator: Arc<C>,
+    partition_key: Arc<K>,
+}
+
+impl<C, K> FnSortKey<C, K>
+where
+    C: Fn(&Row, &Row) -> Ordering + Send + Sync + 'static,
+    K: Fn(&Row) -> Field + Send + Sync + 'static,
+{
+    pub fn new(schema: &Type, comparator: C, partition_key: K) -> Self {
+        Self {
+            message_schema: get_message_type(schema),
+            comparator: Arc::new(comparator),
+            partition_key: Arc::new(partition_key),
+        }
+    }
+}
+
+impl<C, K> SortMultistageParquet for FnSortKey<C, K>
+where
+    C: Fn(&Row, &Row) -> Ordering + Send + Sync + 'static,
+    K: Fn(&Row) -> Field + Send + Sync + 'static,
+{
+    fn get_partition_compare_fn(&self) -> Box<dyn Fn(&Row, &Row) -> Ordering> {
+        let partition_key = Arc::clone(&self.partition_key);
+        Box::new(move |left: &Row, right: &Row| compare_fields(&partition_key(left), &partition_key(right)))
+    }
+
+    fn get_record_compare_fn(&self) -> Box<dyn Fn(&Row, &Row) -> Ordering> {
+        let comparator = Arc::clone(&self.comparator);
+        Box::new(move |left: &Row, right: &Row| comparator(left, right))
+    }
+
+    fn get_partition_filter_fn(&self, partition_row: &Row) -> Box<dyn Fn(&Row) -> bool> {
+        let partition_key = Arc::clone(&self.partition_key);
+        let upper_bound = partition_key(partition_row);
+        Box::new(move |row: &Row| compare_fields(&partition_key(row), &upper_bound) != Ordering::Greater)
+    }
+
+    fn get_partition_message_schema(&self) -> String {
+        self.message_schema.clone()
+    }
+
+    /// Only a label for the progress-messages: closures can not be told apart, so the output of a sort on a 'FnSortKey'
+    /// is never marked as sorted and it can not be checkpointed.
+    fn name(&self) -> &str {
+        "sort_by"
+    }
 }
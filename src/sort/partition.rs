@@ -1,15 +1,14 @@
-use super::parquet_key::{SortMultistageParquet, ParquetKey};
+use super::parquet_key::SortMultistageParquet;
 use crate::rowiterext::read_row_sample;
 use itertools::Itertools;
 use parquet::record::Row;
 
 /// Get a partition over a large dataset
-pub fn partitioning(input_path: &str, parquet_key: &ParquetKey, num_partition: usize) -> Vec<Row> {
-    let single_column_message_type = parquet_key.get_partition_message_schema();
-    // row 'account' should be flexible.
-    let mut sample = read_row_sample(input_path, 1000, &single_column_message_type);
+pub fn partitioning(input_path: &str, sort_key: &dyn SortMultistageParquet, num_partition: usize) -> Vec<Row> {
+    let partition_message_type = sort_key.get_partition_message_schema();
+    let mut sample = read_row_sample(input_path, 1000, &partition_message_type);
 
-    sample.sort_unstable_by(parquet_key.get_partition_compare_fn());
+    sample.sort_unstable_by(sort_key.get_partition_compare_fn());
     let step_size = sample.len() / (num_partition - 1);
     let partition = sample
         .into_iter()
@@ -0,0 +1,154 @@
+use super::dedup::Deduplicator;
+use super::parquet_key::{ParquetKey, SortMultistageParquet};
+use super::sorted_output::SortedOutput;
+use crate::merge::MergeIter;
+use crate::parquet_reader::{get_parquet_reader, RowGroupPredicate};
+use crate::rowiterext::{get_parquet_iter, RowIterExt};
+use crate::sort_metadata::get_sort_metadata;
+use parquet::{file::metadata::RowGroupMetaData, record::Row};
+use std::cmp::Ordering;
+
+/// The extent to which an input is already sorted on the sort-key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sortedness {
+    /// The file is sorted globally, so sorting can be skipped.
+    Sorted,
+    /// Each row-group is sorted, so the row-groups are sorted runs that only need to be merged.
+    SortedRowGroups,
+    Unsorted,
+}
+
+/// Determine whether the input is already sorted on 'parquet_key'.
+/// If the metadata of the file marks it as sorted on the same key no further checks are needed. Otherwise the key-column
+/// (and only that column) is scanned, and the scan stops as soon as a row-group turns out to be unsorted.
+/// The key is matched on its name, as the column-index of 'parquet_key' refers to the (projected) read schema.
+pub fn detect_sortedness(input_path: &str, parquet_key: &ParquetKey) -> Sortedness {
+    if let Some(sort_metadata) = get_sort_metadata(input_path) {
+        if sort_metadata.key == parquet_key.name() && !sort_metadata.descending {
+            return Sortedness::Sorted;
+        }
+    }
+
+    let row_group_sizes: Vec<usize> = get_parquet_reader(input_path)
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|rg| rg.num_rows() as usize)
+        .collect();
+
+    let message_type = parquet_key.get_partition_message_schema();
+    let (keys, _) = get_parquet_iter(input_path, Some(message_type.as_str()))
+        .expect(&format!("Failed to read the key-column of '{input_path}'"));
+    classify_runs(keys, &row_group_sizes, parquet_key.get_partition_compare_fn())
+}
+
+/// Classify a stream of keys, which consists of row-groups with the given sizes.
+fn classify_runs<I>(
+    keys: I,
+    row_group_sizes: &[usize],
+    compare: Box<dyn Fn(&Row, &Row) -> Ordering>,
+) -> Sortedness
+where
+    I: Iterator<Item = Row>,
+{
+    let mut sizes = row_group_sizes.iter();
+    let mut remaining = 0;
+    let mut globally_sorted = true;
+    let mut prev: Option<Row> = None;
+
+    for key in keys {
+        let mut same_row_group = true;
+        while remaining == 0 {
+            remaining = *sizes.next().expect("More rows than reported in the row-group metadata");
+            same_row_group = false;
+        }
+        if let Some(prev) = &prev {
+            if compare(prev, &key) == Ordering::Greater {
+                if same_row_group {
+                    return Sortedness::Unsorted;
+                }
+                globally_sorted = false;
+            }
+        }
+        prev = Some(key);
+        remaining -= 1;
+    }
+
+    if globally_sorted {
+        Sortedness::Sorted
+    } else {
+        Sortedness::SortedRowGroups
+    }
+}
+
+/// Copy an input that is already sorted to the output, only applying the de-duplication. The input is read with the
+/// projection 'message_type'.
+pub fn copy_sorted(input_path: &str, message_type: Option<&str>, mut output: SortedOutput, dedup: Option<&Deduplicator>) {
+    let (rows, _) = get_parquet_iter(input_path, message_type).expect(&format!("Failed to read '{input_path}'"));
+    match dedup {
+        Some(dedup) => dedup.dedup(rows).for_each(|row| output.append_row(row)),
+        None => rows.for_each(|row| output.append_row(row)),
+    }
+    output.close();
+}
+
+/// The maximal number of row-groups that are merged as sorted runs. Each run has a reader of its own (for an S3-object
+/// with a buffer of 10MB), so an input with more row-groups is sorted instead.
+pub const MAX_MERGED_ROW_GROUPS: usize = 32;
+
+/// Sort an input of which each row-group is sorted by merging the row-groups as sorted runs.
+/// Each row-group gets its own reader (with the projection 'message_type'), so only the current row of each run is in memory.
+/// The number of row-groups should not exceed 'MAX_MERGED_ROW_GROUPS'.
+pub fn merge_sorted_row_groups(
+    input_path: &str,
+    message_type: Option<&str>,
+    mut output: SortedOutput,
+    parquet_key: &ParquetKey,
+    dedup: Option<&Deduplicator>,
+) {
+    let num_row_groups = get_parquet_reader(input_path).metadata().num_row_groups();
+    assert!(num_row_groups <= MAX_MERGED_ROW_GROUPS, "Too many row-groups in '{input_path}' to merge them as runs");
+
+    let runs: Vec<RowIterExt> = (0..num_row_groups)
+        .map(|run| {
+            let predicate: RowGroupPredicate = Box::new(move |_: &RowGroupMetaData, idx: usize| idx == run);
+            RowIterExt::new_projected(input_path, message_type, Some(predicate))
+        })
+        .collect();
+    let compare = parquet_key.get_record_compare_fn();
+    let merged = MergeIter::new(runs, |left: &Row, right: &Row| {
+        compare(left, right) != Ordering::Greater
+    });
+
+    match dedup {
+        Some(dedup) => dedup.dedup(merged).for_each(|row| output.append_row(row)),
+        None => merged.for_each(|row| output.append_row(row)),
+    }
+    output.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_runs, Sortedness};
+    use crate::rowwritebuffer::create_row;
+    use parquet::record::{Field, Row, RowAccessor};
+
+    fn classify(keys: Vec<i64>, row_group_sizes: &[usize]) -> Sortedness {
+        let keys = keys
+            .into_iter()
+            .map(|k| create_row(vec![("id".to_owned(), Field::Long(k))]));
+        classify_runs(
+            keys,
+            row_group_sizes,
+            Box::new(|l: &Row, r: &Row| l.get_long(0).unwrap().cmp(&r.get_long(0).unwrap())),
+        )
+    }
+
+    #[test]
+    fn test_classify_runs() {
+        assert_eq!(classify(vec![1, 2, 2, 3, 5], &[2, 3]), Sortedness::Sorted);
+        assert_eq!(classify(vec![1, 4, 2, 3, 5], &[2, 3]), Sortedness::SortedRowGroups);
+        assert_eq!(classify(vec![1, 4, 2, 3, 5], &[3, 2]), Sortedness::Unsorted);
+        assert_eq!(classify(vec![3, 4, 1, 2], &[2, 0, 2]), Sortedness::SortedRowGroups);
+    }
+}
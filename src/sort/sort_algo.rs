@@ -1,36 +1,56 @@
-use super::parquet_key::{SortMultistageParquet, ParquetKey};
+use super::checkpoint::SortCheckpoint;
+use super::dedup::Deduplicator;
+use super::parquet_key::SortMultistageParquet;
 use super::partition::partitioning;
+use super::sorted_output::SortedOutput;
+use super::SortOptions;
+use crate::metadata::get_message_type;
+use crate::object_size::get_object_size;
+use crate::parquet_reader::{get_parquet_reader, RowGroupPredicate};
 use crate::rowiterext::RowIterExt;
 use crate::rowwritebuffer::RowWriteBuffer;
+use crate::scratch::ScratchSpace;
 use itertools::Itertools;
-use parquet::{
-    record::{Row, RowAccessor},
-    schema::types::Type,
+use parquet::{file::metadata::RowGroupMetaData, record::Row, schema::types::Type};
+use std::{
+    cmp::{self, Ordering},
+    sync::{Arc, Mutex},
+    thread,
 };
-use std::{cmp::Ordering, sync::Arc};
 
 const MAX_SORT_BLOCK: u64 = 1_000_000; //10_000  // 1 in REPORT_APPEND_STEP rows is reported on the console.
 
-/// sort the input (parquet-file) in one pass and writer it to the sorted-path
+/// Sort 'data' in memory. A stable sort keeps rows with equal keys in their current order, an unstable sort is faster.
+pub fn sort_rows(data: &mut Vec<Row>, comparator: Box<dyn Fn(&Row, &Row) -> Ordering>, stable: bool) {
+    if stable {
+        data.sort_by(comparator);
+    } else {
+        data.sort_unstable_by(comparator);
+    }
+}
+
+/// sort the input (parquet-file) in one pass and writer it to the 'output'
 /// Internal function: The 'input' iterator is already created by the 'sort' method that selects 'sort_simple' or 'sort_multi_stage'
 pub fn sort_simple(
     mut input: RowIterExt,
-    schema: Arc<Type>,
-    sorted_path: &str,
+    mut output: SortedOutput,
     comparator: Box<dyn Fn(&Row, &Row) -> Ordering>,
+    stable: bool,
+    dedup: Option<&Deduplicator>,
 ) {
-    let mut row_writer = RowWriteBuffer::new(sorted_path, schema, 10000).unwrap();
-
     if let Some(mut data) = input.take(MAX_SORT_BLOCK) {
         if let Some(_) = input.take(1) {
             panic!("the input-file contained more than {MAX_SORT_BLOCK} rows. Use the sort operation instead (multi-stage sort), which can handle huge files");
         };
-        data.sort_by(comparator);
+        sort_rows(&mut data, comparator, stable);
+        if let Some(dedup) = dedup {
+            data = dedup.dedup(data.into_iter()).collect();
+        }
 
-        row_writer.append_row_group(data);
+        output.append_row_group(data);
     };
 
-    row_writer.close();
+    output.close();
 }
 
 /// Stage-1 of the Multi-stage sort. In this stage all data of the input is split to a set of non-overlapping partitions in separate files/objects.
@@ -39,16 +59,17 @@ pub fn sort_ms_stage_1(
     mut input: RowIterExt,
     interm_paths: &Vec<String>,
     schema: Arc<Type>,
-    partition: Vec<Row>,
-    parquet_key: &ParquetKey) {
+    partition: &[Row],
+    sort_key: &dyn SortMultistageParquet,
+    block_size: u64,
+    options: &SortOptions) {
         let mut row_writer: Vec<_> = interm_paths
         .iter()
         .map(|path| RowWriteBuffer::new(&path, Arc::clone(&schema), 10000).unwrap())
         .collect();
 
-    while let Some(mut data) = input.take(MAX_SORT_BLOCK) {
-        data.sort_by(parquet_key.get_record_compare_fn());
-        println!("Retrieved {} rows from input-file", data.len());
+    while let Some(mut data) = input.take(block_size) {
+        sort_rows(&mut data, sort_key.get_record_compare_fn(), options.stable);
 
         let mut i: usize = 0; // skip first field as it is the lowest value and thus seems to be a zero-partition ??
         let mut ready: bool = false;
@@ -61,7 +82,7 @@ pub fn sort_ms_stage_1(
                 }; // early termination as end of iterator is flagged.
 
                 let data: Vec<_> = if i < partition.len() {
-                    let check_in_partition = parquet_key.get_partition_filter_fn(&partition[i]);
+                    let check_in_partition = sort_key.get_partition_filter_fn(&partition[i]);
                     // Using iter.take_while(..) does not work, as it loses the first item of the next partition.
                     // so we implement this alternative
                     let mut data = Vec::new();
@@ -75,85 +96,390 @@ pub fn sort_ms_stage_1(
                     data
                 } else {
                     ready = true; // flag that next iteration should return None
-                    println!("Taking the remaining rows");
                     let data: Vec<_> = it.collect();
                     if data.len() == 0 {
                         return None;
                     };
                     data
                 };
-                println!("partition {i}: collected a dataset of size {}", data.len());
                 // move to next partition
                 let idx = i;
                 i = i + 1;
                 Some((idx, data))
             })
-            .for_each(|(idx, data)| {
-                println!("Now appending a row-group of length: {}", data.len());
-                let ids: Vec<_> = data.iter().map(|r| r.get_long(0)).collect();
-                println!(" The collected ids are: {ids:?}");
-                row_writer[idx].append_row_group(data)
-            })
+            .for_each(|(idx, data)| row_writer[idx].append_row_group(data))
     } 
 
-    println!(
-        "Closing the RowWriteBuffers for base: {}",
-        interm_paths[0].replace('0', "<N>")
-    );
     row_writer.iter_mut().for_each(|rw| rw.close());
 }
 
+/// Determine whether row-group 'idx' belongs to 'worker'. For a stable sort each worker gets a contiguous range of row-groups,
+/// such that concatenating the outputs of the workers in worker-order retains the original input order.
+/// Otherwise the row-groups are distributed round-robin, which balances the load better when row-groups differ in size.
+fn is_worker_row_group(idx: usize, worker: usize, num_workers: usize, num_row_groups: usize, stable: bool) -> bool {
+    if stable {
+        idx * num_workers / num_row_groups == worker
+    } else {
+        idx % num_workers == worker
+    }
+}
+
+/// Select the row-groups 'worker' is responsible for (see 'is_worker_row_group').
+fn worker_row_groups(worker: usize, num_workers: usize, num_row_groups: usize, stable: bool) -> RowGroupPredicate {
+    Box::new(move |_: &RowGroupMetaData, idx: usize| {
+        is_worker_row_group(idx, worker, num_workers, num_row_groups, stable)
+    })
+}
+
+/// Parallel version of Stage-1. Each worker opens its own reader on the input, reads a disjoint subset of the row-groups
+/// and writes its partitions to its own set of intermediate files, so workers never share a RowWriteBuffer.
+/// The sort-block of each worker is reduced such that the total memory use is comparable to the sequential stage-1.
+/// Workers that completed in an earlier run (according to the checkpoint) are skipped.
+fn sort_ms_stage_1_parallel(
+    input_path: &str,
+    interm_paths: &Vec<Vec<String>>,
+    schema: Arc<Type>,
+    partition: &[Row],
+    sort_key: &dyn SortMultistageParquet,
+    options: &SortOptions,
+    checkpoint: &Mutex<SortCheckpoint>) {
+    let num_workers = interm_paths.len();
+    let block_size = cmp::max(1, MAX_SORT_BLOCK / num_workers as u64);
+    let num_row_groups = get_parquet_reader(input_path).metadata().num_row_groups();
+    // the workers read the same columns as the reader that produced 'schema'
+    let message_type = get_message_type(&schema);
+
+    thread::scope(|s| {
+        interm_paths.iter().enumerate().for_each(|(worker, worker_paths)| {
+            if checkpoint.lock().unwrap().is_stage_1_done(worker) {
+                options
+                    .progress
+                    .message(&format!("Worker {worker}: stage-1 already completed in an earlier run"));
+                return;
+            }
+            let schema = Arc::clone(&schema);
+            let message_type = message_type.as_str();
+            s.spawn(move || {
+                let predicate = worker_row_groups(worker, num_workers, num_row_groups, options.stable);
+                let input = RowIterExt::new_projected(input_path, Some(message_type), Some(predicate))
+                    .with_progress(options.progress.clone());
+                sort_ms_stage_1(input, worker_paths, schema, partition, sort_key, block_size, options);
 
-/// Stage-2 of the Multi-stage sort. In this stage all intermediate files/objects are merged to a single outut (file or object).
+                let mut checkpoint = checkpoint.lock().unwrap();
+                checkpoint.mark_stage_1_done(worker);
+                checkpoint.save();
+            });
+        });
+    });
+}
+
+/// Sorts (and optionally de-duplicates) the partitions in stage-2.
+struct PartitionSorter<'a> {
+    sort_key: &'a dyn SortMultistageParquet,
+    stable: bool,
+    dedup: Option<&'a Deduplicator>,
+}
+
+impl<'a> PartitionSorter<'a> {
+    /// Read all rows of the intermediate files that together form a single partition and return these sorted.
+    /// The files are read in worker-order, so for a stable sort the rows with equal keys are still in input order before sorting.
+    /// As all rows with the same key are in the same partition the de-duplication is complete.
+    fn read_sorted_partition(&self, interm_paths: &[&String]) -> Vec<Row> {
+        let mut data = Vec::new();
+        interm_paths.iter().for_each(|interm_path| {
+            if let Some(rows) = RowIterExt::new(interm_path).take(u64::MAX) {
+                data.extend(rows);
+            }
+        });
+        // Sorting can be skipped if the case this partition consists of just one row-group (which can be seen from the meta-data)
+        sort_rows(&mut data, self.sort_key.get_record_compare_fn(), self.stable);
+        match self.dedup {
+            Some(dedup) => dedup.dedup(data.into_iter()).collect(),
+            None => data,
+        }
+    }
+}
+
+/// Group the intermediate paths (indexed as [worker][partition]) per partition.
+fn partition_inputs(interm_paths: &Vec<Vec<String>>) -> Vec<Vec<&String>> {
+    let num_partitions = interm_paths[0].len();
+    (0..num_partitions)
+        .map(|i| interm_paths.iter().map(|worker_paths| &worker_paths[i]).collect())
+        .collect()
+}
+
+
+/// Stage-2 of the Multi-stage sort. In this stage all intermediate files/objects are merged to a single outut ('output').
 /// The intermediate files consists of subsequent partitions. However, these files need to be sorted first as they are not sorted across row-groups 
 /// (As an optimization we could skip the sorting step in case files consist of a single row-group (which can be seen from the meta-data))
+/// The 'interm_paths' are indexed as [worker][partition]. With multiple workers the partitions are read and sorted concurrently,
+/// while the writer appends the partitions in order, such that the output remains ordered.
 fn sort_ms_stage_2(
-    sorted_path: &str,
-    interm_paths: &Vec<String>,
+    mut output: SortedOutput,
+    interm_paths: &Vec<Vec<String>>,
+    sorter: &PartitionSorter,
+    num_workers: usize,
+) {
+    let partitions = partition_inputs(interm_paths);
+
+    if num_workers == 1 {
+        partitions
+            .iter()
+            .for_each(|paths| output.append_row_group(sorter.read_sorted_partition(paths)));
+    } else {
+        partitions.chunks(num_workers).for_each(|chunk| {
+            let sorted_partitions: Vec<Vec<Row>> = thread::scope(|s| {
+                let handles: Vec<_> = chunk
+                    .iter()
+                    .map(|paths| s.spawn(move || sorter.read_sorted_partition(paths)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("Sorting of a partition failed"))
+                    .collect()
+            });
+            sorted_partitions
+                .into_iter()
+                .for_each(|data| output.append_row_group(data));
+        });
+    }
+    output.close();
+}
+
+/// Stage-2 of the Multi-stage sort when checkpointing. Each partition is sorted to its own file in the scratch space ('sorted_paths')
+/// and registered in the checkpoint, such that a resumed run only sorts the remaining partitions.
+/// Finally the sorted partitions are copied to the 'output', which only involves IO.
+fn sort_ms_stage_2_checkpointed(
+    mut output: SortedOutput,
+    interm_paths: &Vec<Vec<String>>,
+    sorted_paths: &Vec<String>,
     schema: Arc<Type>,
-    parquet_key: &ParquetKey
+    sorter: &PartitionSorter,
+    num_workers: usize,
+    checkpoint: &Mutex<SortCheckpoint>,
 ) {
-    let mut row_writer = RowWriteBuffer::new(&sorted_path, Arc::clone(&schema), 10000).unwrap();
+    let partitions = partition_inputs(interm_paths);
+    let todo: Vec<usize> = (0..partitions.len())
+        .filter(|i| !checkpoint.lock().unwrap().is_partition_sorted(*i))
+        .collect();
 
-    interm_paths.iter().for_each(|interm_path| {
-        let mut input = RowIterExt::new(interm_path);
-        let Some(mut data) = input.take(u64::MAX) else {
-                println!("The file '{interm_path}' contains no data-rows.");
-                return;
-            };
-    // Sorting can be skipped if the case this file/object contins just one row-group (which can be seen from the meta-data)
-    data.sort_by(parquet_key.get_record_compare_fn());
-        row_writer.append_row_group(data);
+    let sort_partition = |i: usize| {
+        let data = sorter.read_sorted_partition(&partitions[i]);
+        let mut partition_writer = RowWriteBuffer::new(&sorted_paths[i], Arc::clone(&schema), 10000).unwrap();
+        partition_writer.append_row_group(data);
+        partition_writer.close();
+
+        let mut checkpoint = checkpoint.lock().unwrap();
+        checkpoint.mark_partition_sorted(i);
+        checkpoint.save();
+    };
+    let sort_partition = &sort_partition;
+
+    todo.chunks(num_workers).for_each(|chunk| {
+        if chunk.len() == 1 {
+            sort_partition(chunk[0]);
+        } else {
+            thread::scope(|s| {
+                chunk.iter().for_each(|i| {
+                    let i = *i;
+                    s.spawn(move || sort_partition(i));
+                });
+            });
+        }
+    });
+
+    sorted_paths.iter().for_each(|path| {
+        if let Some(data) = RowIterExt::new(path).take(u64::MAX) {
+            output.append_row_group(data);
+        }
     });
-    row_writer.close();
+    output.close();
 }
 
 /// Sort the input in two passes. The first pass returns a file with sorted row-groups. In the second pass these row-groups are merged.
 /// Internal function: The 'input' iterator is already created by the 'sort' method that selects 'sort_simple' or 'sort_multi_stage'
+/// When 'options.checkpoint' is set the progress is persisted after each step, and a rerun with the same inputs resumes from it.
 pub fn sort_multistage(
-    mut input: RowIterExt,
-    schema: Arc<Type>,
+    input: RowIterExt,
     input_path: &str,
     sorted_path: &str,
-    parquet_key: ParquetKey,
+    output: SortedOutput,
+    sort_key: &dyn SortMultistageParquet,
+    options: &SortOptions,
+    dedup: Option<&Deduplicator>,
 ) {
-    let partition = partitioning(input_path, &parquet_key, 3);
+    let schema = Arc::new(input.schema().clone());
+    let num_workers = options.num_workers;
+    let stable = options.stable;
+
+    // the read schema (on a single line) identifies the projection, as the intermediate files are written with it.
+    let read_schema = get_message_type(&schema).split_whitespace().join(" ");
+    let fingerprint = format!(
+        "{input_path}|{}|{sorted_path}|{}|{num_workers}|{stable}|{read_schema}",
+        get_object_size(input_path),
+        sort_key.name()
+    );
+    let mut checkpoint = SortCheckpoint::new(options.checkpoint.as_deref(), fingerprint);
+    if let (Some(path), Some(_)) = (&options.checkpoint, checkpoint.scratch_dir()) {
+        options.progress.message(&format!("Resuming the sort from checkpoint {path:?}"));
+    }
+
+    // the scratch space removes all intermediate files when it is dropped, also when one of the stages panics.
+    // When checkpointing the scratch space is kept until the sort completes, such that a next run can resume.
+    let mut scratch = match checkpoint.scratch_dir() {
+        Some(run_dir) => ScratchSpace::resume(&options.scratch, run_dir),
+        None => ScratchSpace::new(&options.scratch, "sort"),
+    };
+    if checkpoint.is_enabled() {
+        scratch.set_keep(true);
+        checkpoint.set_scratch_dir(scratch.run_dir());
+    }
+
+    let partition: Vec<Row> = match checkpoint.boundaries() {
+        Some(boundaries) => boundaries
+            .iter()
+            .map(|value| sort_key.parse_partition_value(value))
+            .collect(),
+        None => {
+            // more partitions than workers, such that stage-2 can keep all workers busy.
+            let num_partitions = cmp::max(3, 2 * num_workers);
+            let partition = partitioning(input_path, sort_key, num_partitions);
+            let boundaries: Option<Vec<String>> = partition
+                .iter()
+                .map(|row| sort_key.format_partition_value(row))
+                .collect();
+            if let Some(boundaries) = boundaries {
+                checkpoint.set_boundaries(boundaries);
+            }
+            partition
+        }
+    };
+    checkpoint.save();
 
     let num_row_writer = partition.len() + 1; // Last row_writer is needed to store the tail (N partitions result in N+1 segments.
-    let interm_paths: Vec<_> = (0..num_row_writer)
-        .map(|i| {
-            sorted_path
-                .replace(".parquet", &format!("intermediate-{}.parquet", i))
-                .to_owned()
+    let interm_paths: Vec<Vec<String>> = (0..num_workers)
+        .map(|w| {
+            (0..num_row_writer)
+                .map(|i| scratch.path(&format!("intermediate-{}-{}.parquet", i, w)))
+                .collect()
         })
         .collect();
 
-    println!(
-        "Enter phase-1: writing to intermedidate file(s) {}.<N>",
-        interm_paths[0]
-    );    
-    sort_ms_stage_1(input, &interm_paths, Arc::clone(&schema), partition, &parquet_key);
+    let checkpoint = Mutex::new(checkpoint);
+
+    options.progress.phase(sorted_path, "stage-1");
+    if num_workers == 1 {
+        if checkpoint.lock().unwrap().is_stage_1_done(0) {
+            options.progress.message("Stage-1 already completed in an earlier run");
+        } else {
+            sort_ms_stage_1(input, &interm_paths[0], Arc::clone(&schema), &partition, sort_key, MAX_SORT_BLOCK, options);
+            let mut checkpoint = checkpoint.lock().unwrap();
+            checkpoint.mark_stage_1_done(0);
+            checkpoint.save();
+        }
+    } else {
+        // each worker opens its own reader, so the reader used to obtain the schema is not needed anymore.
+        drop(input);
+        sort_ms_stage_1_parallel(input_path, &interm_paths, Arc::clone(&schema), &partition, sort_key, options, &checkpoint);
+    }
+
+    options.progress.phase(sorted_path, "stage-2");
+    let sorter = PartitionSorter {
+        sort_key,
+        stable,
+        dedup,
+    };
+    let checkpoint = checkpoint.into_inner().unwrap();
+    if checkpoint.is_enabled() {
+        let sorted_paths: Vec<String> = (0..num_row_writer)
+            .map(|i| scratch.path(&format!("sorted-{}.parquet", i)))
+            .collect();
+        let checkpoint = Mutex::new(checkpoint);
+        sort_ms_stage_2_checkpointed(output, &interm_paths, &sorted_paths, schema, &sorter, num_workers, &checkpoint);
+
+        // the sort is completed, so the checkpoint and the scratch space can be removed.
+        checkpoint.into_inner().unwrap().remove();
+        scratch.set_keep(false);
+    } else {
+        sort_ms_stage_2(output, &interm_paths, &sorter, num_workers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_worker_row_group, sort_multistage};
+    use crate::progress::Progress;
+    use crate::rowiterext::{read_rows, RowIterExt};
+    use crate::rowwritebuffer::{create_row, RowWriteBuffer};
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use crate::sort::{parquet_key::ParquetKey, SortOptions, SortedOutput};
+    use parquet::{
+        record::{Field, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    const MESSAGE_TYPE: &str = "message schema { REQUIRED INT64 key; REQUIRED INT64 seq; }";
+
+    /// Sort 'input_path' with the multistage sort and return the (key, seq) of the sorted rows.
+    fn sort_rows_multistage(input_path: &str, sorted_path: &str, num_workers: usize) -> Vec<(i64, i64)> {
+        let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+        let key = ParquetKey::new("key".to_owned(), Arc::clone(&schema));
+        let options = SortOptions {
+            num_workers,
+            ..SortOptions::default()
+        };
+        let output = SortedOutput::new(sorted_path, schema, None, None, Progress::default());
+        sort_multistage(RowIterExt::new(input_path), input_path, sorted_path, output, &key, &options, None);
+
+        read_rows(sorted_path, None, MESSAGE_TYPE)
+            .iter()
+            .map(|row| (row.get_long(0).unwrap(), row.get_long(1).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_multistage_matches_single_worker() {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let input_path = scratch.path("input.parquet");
+        let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+        // 12 row-groups with 101 distinct keys in a shuffled order, so equal keys are spread over the workers.
+        let mut row_writer = RowWriteBuffer::new(&input_path, schema, 250).unwrap();
+        (0..3000).for_each(|seq| {
+            row_writer.append_row(create_row(vec![
+                ("key".to_owned(), Field::Long(seq * 7919 % 101)),
+                ("seq".to_owned(), Field::Long(seq)),
+            ]))
+        });
+        row_writer.close();
+
+        let single = sort_rows_multistage(&input_path, &scratch.path("single.parquet"), 1);
+        let parallel = sort_rows_multistage(&input_path, &scratch.path("parallel.parquet"), 3);
+
+        assert_eq!(single.len(), 3000);
+        // sorted on the key, and stable: equal keys are still in input order.
+        assert!(single.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(parallel, single);
+    }
+
+    #[test]
+    fn test_stable_row_groups_are_contiguous() {
+        let (num_workers, num_row_groups) = (3, 10);
+        let owners: Vec<usize> = (0..num_row_groups)
+            .map(|idx| {
+                (0..num_workers)
+                    .filter(|w| is_worker_row_group(idx, *w, num_workers, num_row_groups, true))
+                    .collect::<Vec<_>>()
+            })
+            .map(|owners| {
+                assert_eq!(owners.len(), 1, "each row-group should have exactly one owner");
+                owners[0]
+            })
+            .collect();
 
-    println!("Move intermediate data to the final file '{sorted_path}'");
-    sort_ms_stage_2(sorted_path, &interm_paths, schema, &parquet_key);
+        // contiguous ranges in worker-order, so concatenation retains the input order.
+        assert!(owners.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(owners[0], 0);
+        assert_eq!(owners[num_row_groups - 1], num_workers - 1);
+    }
 }
@@ -0,0 +1,382 @@
+use crate::{
+    find_field,
+    object_size::get_object_size,
+    parquet_reader::get_parquet_reader,
+    progress::Progress,
+    projection::ColumnProjection,
+    row_compare::{compare_fields, get_field},
+    rowwritebuffer::{create_row, RowWriteBuffer},
+    sort_metadata::SortMetadata,
+};
+use parquet::{
+    basic::{ConvertedType, Type as PhysType},
+    record::{Field, Row},
+    schema::{parser::parse_message_type, types::Type},
+};
+use std::{cmp::Ordering, sync::Arc};
+
+/// Estimate the number of rows that result in files of about 'target_file_bytes', based on the average row-size of the inputs.
+pub fn rows_per_file(input_paths: &[&str], target_file_bytes: u64) -> usize {
+    let num_rows: u64 = input_paths
+        .iter()
+        .map(|path| get_parquet_reader(path).num_rows() as u64)
+        .sum();
+    let num_bytes: u64 = input_paths.iter().map(|path| get_object_size(path)).sum();
+    let bytes_per_row = (num_bytes / num_rows.max(1)).max(1);
+    (target_file_bytes / bytes_per_row).max(1) as usize
+}
+
+/// A file in a range-partitioned output.
+struct RangeFile {
+    path: String,
+    min_key: Option<Field>,
+    max_key: Option<Field>,
+    num_rows: usize,
+}
+
+/// The destination of a sort. Without 'rows_per_file' all rows are written to a single file at 'sorted_path'.
+/// Otherwise the output is split in files '<base>-<NNNNN>.parquet' of about 'rows_per_file' rows, where '<base>' is the
+/// 'sorted_path' without the '.parquet' extension. With 'sort_metadata' a file is only closed at a change of the key, so the
+/// key-ranges of the files do not overlap, and on close a manifest '<base>-manifest.parquet' is written that lists the path,
+/// min_key, max_key and num_rows of each file. Without 'sort_metadata' the rows are split in files of exactly 'rows_per_file' rows.
+/// With a 'projection' the rows are projected before they are written, in which case 'schema' and 'sort_metadata' describe
+/// the projected rows.
+pub struct SortedOutput {
+    base_path: String,
+    schema: Arc<Type>,
+    sort_metadata: Option<SortMetadata>,
+    rows_per_file: Option<usize>,
+    writer: Option<RowWriteBuffer>,
+    current: Option<RangeFile>,
+    files: Vec<RangeFile>,
+    progress: Progress,
+    projection: Option<ColumnProjection>,
+}
+
+impl SortedOutput {
+    pub fn new(
+        sorted_path: &str,
+        schema: Arc<Type>,
+        sort_metadata: Option<SortMetadata>,
+        rows_per_file: Option<usize>,
+        progress: Progress,
+    ) -> Self {
+        let mut output = SortedOutput {
+            base_path: sorted_path.to_owned(),
+            schema,
+            sort_metadata,
+            rows_per_file,
+            writer: None,
+            current: None,
+            files: Vec::new(),
+            progress,
+            projection: None,
+        };
+        if rows_per_file.is_none() {
+            // a single output file is always created, also when the input is empty.
+            output.writer = Some(output.create_writer(sorted_path));
+        }
+        output
+    }
+
+    /// Project the rows on the output columns before writing these.
+    pub fn with_projection(mut self, projection: Option<ColumnProjection>) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    fn create_writer(&self, path: &str) -> RowWriteBuffer {
+        let key_value_metadata = self.sort_metadata.as_ref().map(|sm| sm.to_key_value_metadata());
+        RowWriteBuffer::new_with_progress(
+            path,
+            Arc::clone(&self.schema),
+            10000,
+            key_value_metadata,
+            self.progress.clone(),
+        )
+        .unwrap()
+    }
+
+    fn base(&self) -> &str {
+        self.base_path
+            .strip_suffix(".parquet")
+            .unwrap_or(&self.base_path)
+    }
+
+    fn sort_metadata(&self) -> &SortMetadata {
+        self.sort_metadata.as_ref().unwrap()
+    }
+
+    fn key<'a>(&self, row: &'a Row) -> &'a Field {
+        get_field(row, self.sort_metadata().column)
+    }
+
+    /// Open the next range-file if no file is open.
+    fn ensure_file(&mut self) {
+        if self.writer.is_none() {
+            let path = format!("{}-{:05}.parquet", self.base(), self.files.len());
+            self.writer = Some(self.create_writer(&path));
+            self.current = Some(RangeFile {
+                path,
+                min_key: None,
+                max_key: None,
+                num_rows: 0,
+            });
+        }
+    }
+
+    fn close_file(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            writer.close();
+        }
+        if let Some(current) = self.current.take() {
+            self.files.push(current);
+        }
+    }
+
+    fn current_rows(&self) -> usize {
+        self.current.as_ref().map_or(0, |current| current.num_rows)
+    }
+
+    fn is_new_key(&self, row: &Row) -> bool {
+        if self.sort_metadata.is_none() {
+            return true;
+        }
+        match self.current.as_ref().and_then(|current| current.max_key.as_ref()) {
+            Some(max_key) => compare_fields(max_key, self.key(row)) != Ordering::Equal,
+            None => true,
+        }
+    }
+
+    /// Register rows that are written to the current range-file.
+    fn track(&mut self, first: &Row, last: &Row, num_rows: usize) {
+        let keys = self
+            .sort_metadata
+            .as_ref()
+            .map(|_| (self.key(first).clone(), self.key(last).clone()));
+        if let Some(current) = self.current.as_mut() {
+            if let Some((min_key, max_key)) = keys {
+                current.min_key.get_or_insert(min_key);
+                current.max_key = Some(max_key);
+            }
+            current.num_rows += num_rows;
+        }
+    }
+
+    /// The number of rows of 'rows' that still go to the current file: the remaining room, extended up to the next change of the key.
+    fn split_point(&self, rows: &[Row], rows_per_file: usize) -> usize {
+        let room = rows_per_file.saturating_sub(self.current_rows());
+        if rows.len() <= room {
+            return rows.len();
+        }
+        if self.sort_metadata.is_none() || (room == 0 && self.is_new_key(&rows[0])) {
+            return room;
+        }
+        (room.max(1)..rows.len())
+            .find(|i| compare_fields(self.key(&rows[*i - 1]), self.key(&rows[*i])) != Ordering::Equal)
+            .unwrap_or(rows.len())
+    }
+
+    pub fn append_row(&mut self, row: Row) {
+        let row = match &self.projection {
+            Some(projection) => projection.map_row(&row),
+            None => row,
+        };
+        if let Some(rows_per_file) = self.rows_per_file {
+            if self.current_rows() >= rows_per_file && self.is_new_key(&row) {
+                self.close_file();
+            }
+            self.ensure_file();
+            self.track(&row, &row, 1);
+        }
+        self.writer.as_mut().unwrap().append_row(row);
+    }
+
+    pub fn append_row_group(&mut self, mut rows: Vec<Row>) {
+        if let Some(projection) = &self.projection {
+            rows = rows.iter().map(|row| projection.map_row(row)).collect();
+        }
+        let Some(rows_per_file) = self.rows_per_file else {
+            self.writer.as_mut().unwrap().append_row_group(rows);
+            return;
+        };
+
+        while !rows.is_empty() {
+            let split = self.split_point(&rows, rows_per_file);
+            let rest = rows.split_off(split);
+            if !rows.is_empty() {
+                self.ensure_file();
+                self.track(&rows[0], &rows[rows.len() - 1], rows.len());
+                self.writer.as_mut().unwrap().append_row_group(rows);
+            }
+            if !rest.is_empty() {
+                self.close_file();
+            }
+            rows = rest;
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.close_file();
+        if self.rows_per_file.is_some() && self.sort_metadata.is_some() {
+            self.write_manifest();
+        }
+    }
+
+    fn manifest_schema(&self) -> Arc<Type> {
+        let (_, key_type) = find_field(Arc::clone(&self.schema), &self.sort_metadata().key);
+        let phys_type = match key_type.get_physical_type() {
+            PhysType::BYTE_ARRAY => "BINARY".to_owned(),
+            other => other.to_string(),
+        };
+        let converted_type = match key_type.get_basic_info().converted_type() {
+            ConvertedType::NONE => "".to_owned(),
+            other => format!(" ({other})"),
+        };
+        let message_type = format!(
+            "message manifest {{
+                REQUIRED BINARY path (UTF8);
+                REQUIRED {phys_type} min_key{converted_type};
+                REQUIRED {phys_type} max_key{converted_type};
+                REQUIRED INT64 num_rows;
+            }}"
+        );
+        Arc::new(parse_message_type(&message_type).unwrap())
+    }
+
+    fn write_manifest(&mut self) {
+        let manifest_path = format!("{}-manifest.parquet", self.base());
+        let rows: Vec<Row> = self
+            .files
+            .iter()
+            .filter(|file| file.num_rows > 0)
+            .map(|file| {
+                create_row(vec![
+                    ("path".to_owned(), Field::Str(file.path.clone())),
+                    ("min_key".to_owned(), file.min_key.clone().unwrap()),
+                    ("max_key".to_owned(), file.max_key.clone().unwrap()),
+                    ("num_rows".to_owned(), Field::Long(file.num_rows as i64)),
+                ])
+            })
+            .collect();
+
+        let mut manifest_writer =
+            RowWriteBuffer::new_with_progress(&manifest_path, self.manifest_schema(), 10000, None, self.progress.clone())
+                .unwrap();
+        manifest_writer.append_row_group(rows);
+        manifest_writer.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedOutput;
+    use crate::progress::Progress;
+    use crate::rowiterext::read_rows;
+    use crate::rowwritebuffer::create_row;
+    use crate::scratch::{ScratchLocation, ScratchSpace};
+    use crate::sort_metadata::SortMetadata;
+    use parquet::{
+        record::{Field, Row, RowAccessor},
+        schema::parser::parse_message_type,
+    };
+    use std::{path::Path, sync::Arc};
+
+    const MESSAGE_TYPE: &str = "message schema { REQUIRED INT64 key; REQUIRED INT64 seq; }";
+    const MANIFEST_TYPE: &str = "message manifest {
+        REQUIRED BINARY path (UTF8); REQUIRED INT64 min_key; REQUIRED INT64 max_key; REQUIRED INT64 num_rows; }";
+
+    fn rows(keys: &[i64]) -> Vec<Row> {
+        keys.iter()
+            .enumerate()
+            .map(|(seq, key)| {
+                create_row(vec![
+                    ("key".to_owned(), Field::Long(*key)),
+                    ("seq".to_owned(), Field::Long(seq as i64)),
+                ])
+            })
+            .collect()
+    }
+
+    /// Write the 'keys' in two row-groups and a single row, and return the keys of each output file and the manifest
+    /// as (min_key, max_key, num_rows).
+    fn write_split(keys: &[i64], rows_per_file: usize, sorted: bool) -> (Vec<Vec<i64>>, Vec<(i64, i64, i64)>) {
+        let mut scratch = ScratchSpace::new(&ScratchLocation::default(), "test");
+        let sorted_path = scratch.path("sorted.parquet");
+        let base = sorted_path.strip_suffix(".parquet").unwrap().to_owned();
+        let sort_metadata = sorted.then(|| SortMetadata {
+            key: "key".to_owned(),
+            column: 0,
+            descending: false,
+            algorithm: "simple".to_owned(),
+        });
+        let schema = Arc::new(parse_message_type(MESSAGE_TYPE).unwrap());
+        let mut output =
+            SortedOutput::new(&sorted_path, schema, sort_metadata, Some(rows_per_file), Progress::default());
+        let mut rows = rows(keys);
+        let last = rows.pop().unwrap();
+        let second = rows.split_off(rows.len() / 2);
+        output.append_row_group(rows);
+        output.append_row_group(second);
+        output.append_row(last);
+        output.close();
+
+        let files: Vec<Vec<i64>> = (0..)
+            .map(|idx| format!("{base}-{idx:05}.parquet"))
+            .take_while(|path| Path::new(path).exists())
+            .map(|path| {
+                read_rows(&path, None, MESSAGE_TYPE)
+                    .iter()
+                    .map(|row| row.get_long(0).unwrap())
+                    .collect()
+            })
+            .collect();
+        let manifest_path = format!("{base}-manifest.parquet");
+        let manifest = if Path::new(&manifest_path).exists() {
+            read_rows(&manifest_path, None, MANIFEST_TYPE)
+                .iter()
+                .map(|row| (row.get_long(1).unwrap(), row.get_long(2).unwrap(), row.get_long(3).unwrap()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (files, manifest)
+    }
+
+    #[test]
+    fn test_equal_keys_are_not_split() {
+        // the run of key 2 crosses the boundary of 4 rows, so the first file is extended up to the end of the run.
+        let (files, manifest) = write_split(&[1, 1, 2, 2, 2, 2, 2, 3, 4, 4, 5], 4, true);
+        assert_eq!(files, vec![vec![1, 1, 2, 2, 2, 2, 2], vec![3, 4, 4, 5]]);
+        assert_eq!(manifest, vec![(1, 2, 7), (3, 5, 4)]);
+        assert!(manifest.windows(2).all(|w| w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn test_manifest_ranges_do_not_overlap() {
+        let keys: Vec<i64> = (0..50).map(|i| i / 3).collect();
+        let (files, manifest) = write_split(&keys, 5, true);
+        assert_eq!(files.concat(), keys);
+        assert_eq!(manifest.len(), files.len());
+        files.iter().zip(&manifest).for_each(|(file, (min_key, max_key, num_rows))| {
+            assert_eq!((file[0], file[file.len() - 1], file.len() as i64), (*min_key, *max_key, *num_rows));
+            assert!(file.len() >= 5 || file == files.last().unwrap());
+        });
+        assert!(manifest.windows(2).all(|w| w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn test_single_file() {
+        let (files, manifest) = write_split(&[1, 2, 2, 3], 100, true);
+        assert_eq!(files, vec![vec![1, 2, 2, 3]]);
+        assert_eq!(manifest, vec![(1, 3, 4)]);
+    }
+
+    #[test]
+    fn test_split_without_sort_metadata() {
+        // without a sort-key the files get exactly 'rows_per_file' rows, and no manifest is written.
+        let (files, manifest) = write_split(&[1, 1, 1, 1, 1], 2, false);
+        assert_eq!(files, vec![vec![1, 1], vec![1, 1], vec![1]]);
+        assert!(manifest.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{
+    find_column, find_field,
+    parquet_reader::{get_parquet_reader, RowGroupPredicate},
+    row_compare::{compare_fields, get_field},
+    rowiterext::RowIterExt,
+    rowwritebuffer::RowWriteBuffer,
+    sort_metadata::SortMetadata,
+    statistics::{row_group_min_max, StatValue},
+};
+use parquet::{
+    file::metadata::RowGroupMetaData,
+    record::{Field, Row},
+};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::Arc,
+};
+
+/// A row in the top-N heap. Entries are ordered from best to worst, so the max-heap has the worst entry on top.
+/// Ties on the key are resolved on the input order ('seq'), such that the earliest rows are kept.
+struct TopEntry {
+    row: Row,
+    seq: usize,
+    key_col: usize,
+    largest: bool,
+}
+
+impl TopEntry {
+    fn key(&self) -> &Field {
+        get_field(&self.row, self.key_col)
+    }
+}
+
+impl Ord for TopEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ordering = compare_fields(self.key(), other.key());
+        let ordering = if self.largest { ordering.reverse() } else { ordering };
+        ordering.then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for TopEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TopEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TopEntry {}
+
+/// Bounded collection of the N best rows seen so far.
+struct TopN {
+    heap: BinaryHeap<TopEntry>,
+    n: usize,
+    key_col: usize,
+    /// The leaf-index of the key-column, which selects its statistics in the row-groups.
+    key_leaf: usize,
+    largest: bool,
+    seq: usize,
+}
+
+impl TopN {
+    /// Create the collection for the N best of 'num_rows' rows, where N can exceed the number of rows.
+    fn new(n: usize, num_rows: usize, key_col: usize, key_leaf: usize, largest: bool) -> Self {
+        TopN {
+            heap: BinaryHeap::with_capacity(n.min(num_rows)),
+            n,
+            key_col,
+            key_leaf,
+            largest,
+            seq: 0,
+        }
+    }
+
+    fn offer(&mut self, row: Row) {
+        let entry = TopEntry {
+            row,
+            seq: self.seq,
+            key_col: self.key_col,
+            largest: self.largest,
+        };
+        self.seq += 1;
+
+        if self.heap.len() < self.n {
+            self.heap.push(entry);
+        } else if entry < *self.heap.peek().unwrap() {
+            self.heap.pop();
+            self.heap.push(entry);
+        }
+    }
+
+    /// Account for rows that are skipped, such that 'seq' remains the position in the input.
+    fn skip(&mut self, num_rows: usize) {
+        self.seq += num_rows;
+    }
+
+    /// The key of the worst row that is still kept, once N rows are collected.
+    fn threshold(&self) -> Option<&Field> {
+        if self.heap.len() < self.n {
+            return None;
+        }
+        self.heap.peek().map(|entry| entry.key())
+    }
+
+    /// Check on the statistics whether a row-group can contain rows that are better than the current threshold.
+    /// A row equal to the threshold comes later in the input, so it would not be kept either.
+    fn can_contribute(&self, row_group: &RowGroupMetaData) -> bool {
+        let Some(threshold) = self.threshold().and_then(StatValue::from_field) else {
+            return true;
+        };
+        let Some((min, max)) = row_group_min_max(row_group, self.key_leaf) else {
+            return true;
+        };
+        if self.largest {
+            max.compare(&threshold) != Some(Ordering::Less) && max != threshold
+        } else {
+            min.compare(&threshold) != Some(Ordering::Greater) && min != threshold
+        }
+    }
+
+    /// The collected rows from best to worst.
+    fn into_sorted_rows(self) -> Vec<Row> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|entry| entry.row)
+            .collect()
+    }
+}
+
+/// Write the 'n' rows with the smallest (or with 'largest' the largest) value of 'sort_field_name' to 'output_path'.
+/// The rows are streamed through a bounded heap, so only N rows are kept in memory. When N rows are collected, row-groups
+/// of which the min/max statistics show that they can not contain a better row are skipped without reading them.
+/// The output is ordered from best to worst, so descending when selecting the largest rows.
+pub fn sort_top_n(input_path: &str, output_path: &str, sort_field_name: &str, n: usize, largest: bool) {
+    assert!(n > 0, "sort_top_n requires n > 0");
+
+    let reader = get_parquet_reader(input_path);
+    let metadata = reader.metadata();
+    let schema = Arc::new(metadata.file_metadata().schema().clone());
+    let (key_col, _) = find_column(Arc::clone(&schema), sort_field_name);
+    let (key_leaf, _) = find_field(Arc::clone(&schema), sort_field_name);
+
+    let mut top_n = TopN::new(n, metadata.file_metadata().num_rows() as usize, key_col, key_leaf, largest);
+    for (idx, row_group) in metadata.row_groups().iter().enumerate() {
+        if !top_n.can_contribute(row_group) {
+            top_n.skip(row_group.num_rows() as usize);
+            continue;
+        }
+        // the threshold changes while reading, so each row-group that can contribute gets a reader of its own.
+        let predicate: RowGroupPredicate = Box::new(move |_: &RowGroupMetaData, i: usize| i == idx);
+        let mut rows = RowIterExt::new_filtered(input_path, Some(predicate));
+        while rows.head().is_some() {
+            let (row, _) = rows.update_head();
+            top_n.offer(row);
+        }
+    }
+    let sort_metadata = SortMetadata {
+        key: sort_field_name.to_owned(),
+        column: key_col,
+        descending: largest,
+        algorithm: "top-n".to_owned(),
+    };
+    let mut row_writer = RowWriteBuffer::new_with_metadata(
+        output_path,
+        schema,
+        10000,
+        Some(sort_metadata.to_key_value_metadata()),
+    )
+    .unwrap();
+    row_writer.append_row_group(top_n.into_sorted_rows());
+    row_writer.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopN;
+    use crate::rowwritebuffer::create_row;
+    use parquet::record::{Field, RowAccessor};
+
+    fn top_n(keys: &[i64], n: usize, largest: bool) -> Vec<(i64, i32)> {
+        let mut top_n = TopN::new(n, keys.len(), 0, 0, largest);
+        keys.iter().enumerate().for_each(|(pos, key)| {
+            top_n.offer(create_row(vec![
+                ("id".to_owned(), Field::Long(*key)),
+                ("pos".to_owned(), Field::Int(pos as i32)),
+            ]))
+        });
+        top_n
+            .into_sorted_rows()
+            .iter()
+            .map(|row| (row.get_long(0).unwrap(), row.get_int(1).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_top_n() {
+        let keys = [5, 1, 7, 1, 3, 7];
+        assert_eq!(top_n(&keys, 3, false), vec![(1, 1), (1, 3), (3, 4)]);
+        assert_eq!(top_n(&keys, 2, true), vec![(7, 2), (7, 5)]);
+        assert_eq!(top_n(&keys, 10, false).len(), keys.len());
+    }
+}
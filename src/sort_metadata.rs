@@ -0,0 +1,81 @@
+use crate::parquet_reader::get_parquet_reader;
+use parquet::file::metadata::KeyValue;
+
+// Keys used in the key-value metadata of the footer of sorted files.
+const SORT_KEY: &str = "parquet_ops.sort.key";
+const SORT_COLUMN: &str = "parquet_ops.sort.column";
+const SORT_DIRECTION: &str = "parquet_ops.sort.direction";
+const SORT_ALGORITHM: &str = "parquet_ops.sort.algorithm";
+
+const ASCENDING: &str = "ascending";
+const DESCENDING: &str = "descending";
+
+/// Describes on which key a file is sorted. It is stored in the key-value metadata of the files written by 'sort' (and merges of sorted files),
+/// such that consumers can discover the ordering instead of having to know the comparator.
+/// Only this key-value metadata is written: the 'sorting_columns' of the row-groups are left empty, as parquet 30 offers no way
+/// to set these. So readers that only look at 'sorting_columns' (for example query engines) do not see that the file is sorted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SortMetadata {
+    /// name of the sort-column
+    pub key: String,
+    /// position of the sort-column among the top-level columns of the schema, which is its position in the rows
+    pub column: usize,
+    pub descending: bool,
+    /// the algorithm that produced the file, for example 'simple', 'multistage' or 'merge'
+    pub algorithm: String,
+}
+
+impl SortMetadata {
+    pub fn to_key_value_metadata(&self) -> Vec<KeyValue> {
+        let direction = if self.descending { DESCENDING } else { ASCENDING };
+        vec![
+            KeyValue::new(SORT_KEY.to_owned(), self.key.clone()),
+            KeyValue::new(SORT_COLUMN.to_owned(), self.column.to_string()),
+            KeyValue::new(SORT_DIRECTION.to_owned(), direction.to_owned()),
+            KeyValue::new(SORT_ALGORITHM.to_owned(), self.algorithm.clone()),
+        ]
+    }
+
+    /// Extract the sort-metadata from the key-value metadata of a file. Returns None if the file is not marked as sorted.
+    pub fn from_key_value_metadata(key_value_metadata: Option<&Vec<KeyValue>>) -> Option<Self> {
+        let key_value_metadata = key_value_metadata?;
+        let get = |key: &str| {
+            key_value_metadata
+                .iter()
+                .find(|kv| kv.key == key)
+                .and_then(|kv| kv.value.clone())
+        };
+
+        Some(SortMetadata {
+            key: get(SORT_KEY)?,
+            column: get(SORT_COLUMN)?.parse().ok()?,
+            descending: get(SORT_DIRECTION).map_or(false, |d| d == DESCENDING),
+            algorithm: get(SORT_ALGORITHM).unwrap_or_default(),
+        })
+    }
+}
+
+/// Read the sort-metadata from the footer of the file or object at 'path'.
+pub fn get_sort_metadata(path: &str) -> Option<SortMetadata> {
+    let metadata = get_parquet_reader(path).metadata();
+    SortMetadata::from_key_value_metadata(metadata.file_metadata().key_value_metadata())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortMetadata;
+
+    #[test]
+    fn test_sort_metadata_roundtrip() {
+        let sort_metadata = SortMetadata {
+            key: "account".to_owned(),
+            column: 1,
+            descending: false,
+            algorithm: "multistage".to_owned(),
+        };
+        let kv = sort_metadata.to_key_value_metadata();
+        assert_eq!(SortMetadata::from_key_value_metadata(Some(&kv)), Some(sort_metadata));
+        assert_eq!(SortMetadata::from_key_value_metadata(None), None);
+        assert_eq!(SortMetadata::from_key_value_metadata(Some(&Vec::new())), None);
+    }
+}
@@ -0,0 +1,138 @@
+use parquet::{
+    basic::{ConvertedType, LogicalType, Type as PhysicalType},
+    file::{metadata::RowGroupMetaData, statistics::Statistics},
+    record::Field,
+    schema::types::ColumnDescriptor,
+};
+use std::cmp::Ordering;
+
+/// A value of a column-statistic or a row-field, mapped to the physical domain such that both can be compared.
+/// For example a 'TIMESTAMP_MILLIS' field is stored as INT64, so its statistics are INT64 values.
+/// Unsigned columns get their own kind, as their values (and statistics) do not fit the signed domain.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bytes(Vec<u8>),
+}
+
+impl StatValue {
+    /// Map a row-field to its physical value. Returns None for nulls and nested fields.
+    pub fn from_field(field: &Field) -> Option<StatValue> {
+        match field {
+            Field::Bool(v) => Some(StatValue::Bool(*v)),
+            Field::Byte(v) => Some(StatValue::Int(*v as i64)),
+            Field::Short(v) => Some(StatValue::Int(*v as i64)),
+            Field::Int(v) => Some(StatValue::Int(*v as i64)),
+            Field::Long(v) => Some(StatValue::Int(*v)),
+            Field::UByte(v) => Some(StatValue::UInt(*v as u64)),
+            Field::UShort(v) => Some(StatValue::UInt(*v as u64)),
+            Field::UInt(v) => Some(StatValue::UInt(*v as u64)),
+            Field::ULong(v) => Some(StatValue::UInt(*v)),
+            Field::Date(v) => Some(StatValue::Int(*v as i64)),
+            Field::TimestampMillis(v) => Some(StatValue::Int(*v as i64)),
+            Field::TimestampMicros(v) => Some(StatValue::Int(*v as i64)),
+            Field::Float(v) => Some(StatValue::Float(*v as f64)),
+            Field::Double(v) => Some(StatValue::Float(*v)),
+            Field::Str(v) => Some(StatValue::Bytes(v.as_bytes().to_vec())),
+            Field::Bytes(v) => Some(StatValue::Bytes(v.data().to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Compare two values, where signed and unsigned integers are compared on their value.
+    /// Returns None if the values are of a different kind.
+    pub fn compare(&self, other: &StatValue) -> Option<Ordering> {
+        match (self, other) {
+            (StatValue::Bool(l), StatValue::Bool(r)) => l.partial_cmp(r),
+            (StatValue::Int(l), StatValue::Int(r)) => l.partial_cmp(r),
+            (StatValue::UInt(l), StatValue::UInt(r)) => l.partial_cmp(r),
+            (StatValue::Int(l), StatValue::UInt(r)) => Some(u64::try_from(*l).map_or(Ordering::Less, |l| l.cmp(r))),
+            (StatValue::UInt(_), StatValue::Int(_)) => other.compare(self).map(Ordering::reverse),
+            (StatValue::Float(l), StatValue::Float(r)) => l.partial_cmp(r),
+            (StatValue::Bytes(l), StatValue::Bytes(r)) => l.partial_cmp(r),
+            _ => None,
+        }
+    }
+}
+
+/// Get the (min, max) of the statistics, or None if the statistics do not contain a min/max.
+pub fn statistics_min_max(statistics: &Statistics) -> Option<(StatValue, StatValue)> {
+    if !statistics.has_min_max_set() {
+        return None;
+    }
+    match statistics {
+        Statistics::Boolean(s) => Some((StatValue::Bool(*s.min()), StatValue::Bool(*s.max()))),
+        Statistics::Int32(s) => Some((StatValue::Int(*s.min() as i64), StatValue::Int(*s.max() as i64))),
+        Statistics::Int64(s) => Some((StatValue::Int(*s.min()), StatValue::Int(*s.max()))),
+        Statistics::Float(s) => Some((StatValue::Float(*s.min() as f64), StatValue::Float(*s.max() as f64))),
+        Statistics::Double(s) => Some((StatValue::Float(*s.min()), StatValue::Float(*s.max()))),
+        Statistics::ByteArray(s) => Some((
+            StatValue::Bytes(s.min().data().to_vec()),
+            StatValue::Bytes(s.max().data().to_vec()),
+        )),
+        _ => None,
+    }
+}
+
+/// Check whether the column stores unsigned integers in an INT32 or INT64.
+fn is_unsigned(column: &ColumnDescriptor) -> bool {
+    matches!(
+        column.converted_type(),
+        ConvertedType::UINT_8 | ConvertedType::UINT_16 | ConvertedType::UINT_32 | ConvertedType::UINT_64
+    ) || matches!(column.logical_type(), Some(LogicalType::Integer { is_signed: false, .. }))
+}
+
+/// Reinterpret the statistic of an unsigned column, which is stored with the bits of the signed physical type.
+fn to_unsigned(value: StatValue, physical_type: PhysicalType) -> StatValue {
+    match value {
+        StatValue::Int(v) if physical_type == PhysicalType::INT32 => StatValue::UInt(v as i32 as u32 as u64),
+        StatValue::Int(v) => StatValue::UInt(v as u64),
+        other => other,
+    }
+}
+
+/// Get the (min, max) of (leaf) column 'col' in a row-group, if the row-group has statistics for that column.
+/// Note that 'col' is the index of the column-chunk (see 'find_field'), which differs from the position of the column
+/// in the rows (see 'find_column') when a group precedes it.
+pub fn row_group_min_max(row_group: &RowGroupMetaData, col: usize) -> Option<(StatValue, StatValue)> {
+    let chunk = row_group.column(col);
+    let (min, max) = chunk.statistics().and_then(statistics_min_max)?;
+    if is_unsigned(chunk.column_descr()) {
+        let physical_type = chunk.column_type();
+        Some((to_unsigned(min, physical_type), to_unsigned(max, physical_type)))
+    } else {
+        Some((min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_unsigned, StatValue};
+    use parquet::{basic::Type as PhysicalType, record::Field};
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_stat_value_from_field() {
+        let ts = StatValue::from_field(&Field::TimestampMillis(20)).unwrap();
+        assert_eq!(ts.compare(&StatValue::Int(10)), Some(Ordering::Greater));
+
+        let account = StatValue::from_field(&Field::Str("abc".to_owned())).unwrap();
+        assert_eq!(account.compare(&StatValue::Bytes(b"abd".to_vec())), Some(Ordering::Less));
+        assert_eq!(account.compare(&StatValue::Int(1)), None);
+        assert_eq!(StatValue::from_field(&Field::Null), None);
+    }
+
+    #[test]
+    fn test_stat_value_unsigned() {
+        let large = StatValue::from_field(&Field::ULong(u64::MAX)).unwrap();
+        assert_eq!(large, StatValue::UInt(u64::MAX));
+        assert_eq!(large.compare(&StatValue::UInt(1)), Some(Ordering::Greater));
+        assert_eq!(large.compare(&StatValue::Int(-1)), Some(Ordering::Greater));
+        assert_eq!(StatValue::Int(-1).compare(&StatValue::UInt(0)), Some(Ordering::Less));
+        assert_eq!(StatValue::Int(7).compare(&StatValue::UInt(7)), Some(Ordering::Equal));
+        assert_eq!(to_unsigned(StatValue::Int(-1), PhysicalType::INT32), StatValue::UInt(u32::MAX as u64));
+    }
+}
@@ -19,7 +19,7 @@ pub fn write_parquet(
 
     let schema = ttypes::get_test_schema(extra_columns.try_into().unwrap());
 
-    let mut pw = parquet_writer::get_parquet_writer(path, schema.clone());
+    let mut pw = parquet_writer::get_parquet_writer(path, schema.clone(), None);
 
     // Next code should be the alternative if we have prepared rows.
     // would need some more refactoring to work with RowWriteBuffer